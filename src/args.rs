@@ -1,3 +1,6 @@
+use crate::filter::Strategy;
+use crate::meta::{Chromaticities, Srgb, Time};
+use crate::png::CopyPolicy;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -23,27 +26,343 @@ pub enum Commands {
     Remove(RemoveArgs),
     /// Prints all of the chunks in a PNG file
     Print(PrintArgs),
+    /// Builds a PNG file from a JSON description of its chunks
+    #[cfg(feature = "serde")]
+    Build(BuildArgs),
+    /// Shows a hexdump of the data of a single chunk
+    Inspect(InspectArgs),
+    /// Lists and edits textual metadata (tEXt, zTXt and iTXt chunks)
+    Text(TextArgs),
+    /// Prints and edits image metadata (tIME, pHYs, gAMA, cHRM, sRGB and iCCP chunks)
+    Meta(MetaArgs),
+    /// Lists and redacts the EXIF tags of an eXIf chunk
+    Exif(ExifArgs),
+    /// Removes all ancillary and non-standard chunks except the ones explicitly kept
+    Strip(StripArgs),
+    /// Embeds files as a zip archive, so the image also opens with unzip
+    Polyglot(PolyglotArgs),
+    /// Recompresses the image data, trying several filters and deflate levels
+    Optimize(OptimizeArgs),
+    /// Looks for hidden data and prints a suspicion score per detection method
+    Detect(DetectArgs),
+    /// Compares the chunks of two PNG files, and optionally their pixels
+    Diff(DiffArgs),
+    /// Copies ancillary chunks from another PNG file, e.g. after an editor dropped them
+    Transplant(TransplantArgs),
+    /// Moves a chunk to another position
+    Move(MoveArgs),
+    /// Changes the type of a chunk, recomputing its CRC
+    Rename(RenameArgs),
+    /// Replaces the data of a chunk with the contents of a file
+    ReplaceData(ReplaceDataArgs),
 }
 
 #[derive(Debug, StructOpt)]
 pub struct EncodeArgs {
-    /// Chunk type
-    pub chunk_type: String,
+    /// Chunk type, `key:<secret>` to derive an inconspicuous type from a key,
+    /// `vendor[:<secret>]` to reuse a chunk type of a real image editor,
+    /// `text:<keyword>` to hide the encrypted message in a text chunk, e.g.
+    /// `text:Comment` or `text:XML:com.adobe.xmp`, `lsb` to hide it in the
+    /// least significant bits of the pixels, `alpha` to hide it in the colors
+    /// of transparent pixels (`alpha:lsb` also uses the alpha of the others),
+    /// or `palette` to hide it in the parity of the palette indices of an
+    /// indexed image
+    #[structopt(required_unless = "trailer")]
+    pub chunk_type: Option<String>,
     /// Secret message
-    pub message: String,
+    #[structopt(required_unless = "trailer")]
+    pub message: Option<String>,
+    /// Allows critical, public and standard chunk types
+    #[structopt(short, long)]
+    pub force: bool,
+    /// Encrypts the message with a key derived from the password
+    #[structopt(short, long)]
+    pub password: Option<String>,
+    /// Stores the message after IEND instead of in a chunk
+    #[structopt(long, value_name = "message", conflicts_with_all = &["chunk-type", "message"])]
+    pub trailer: Option<String>,
+    /// Writes interlaced images without interlacing when the pixels change
+    #[structopt(long)]
+    pub deinterlace: bool,
+    /// Handling of unknown unsafe-to-copy chunks when the pixels change: keep, warn or drop
+    #[structopt(long, default_value = "drop")]
+    pub unsafe_chunks: CopyPolicy,
+    /// Matrix embedding for lsb, alpha and palette: hides k bits in every
+    /// 2^k - 1 pixel bits, changing at most one. Higher values change fewer
+    /// pixels but hold less, 1 is plain embedding
+    #[structopt(short, long, value_name = "k", default_value = "1")]
+    pub matrix: u32,
+    /// Adds Reed-Solomon parity bytes to every 255 byte block, so decode can
+    /// repair up to half as many damaged bytes per block. Only for lsb, alpha,
+    /// palette and --trailer: a damaged chunk already fails its CRC check
+    #[structopt(long, value_name = "bytes")]
+    pub ecc: Option<usize>,
 }
 
 #[derive(Debug, StructOpt)]
 pub struct DecodeArgs {
-    /// Chunk type
-    pub chunk_type: String,
+    /// Chunk type, `key:<secret>`, `vendor:<secret>`, `text:<keyword>`,
+    /// `lsb`, `alpha[:lsb]` or `palette` as given to encode; after a bare
+    /// `vendor` give the chunk type encode reported
+    #[structopt(required_unless = "trailer")]
+    pub chunk_type: Option<String>,
+    /// Password the message was encrypted with
+    #[structopt(short, long)]
+    pub password: Option<String>,
+    /// Reads the message stored after IEND
+    #[structopt(long, conflicts_with = "chunk-type")]
+    pub trailer: bool,
+    /// Matrix embedding parameter the message was encoded with
+    #[structopt(short, long, value_name = "k", default_value = "1")]
+    pub matrix: u32,
+    /// Parity bytes per block the message was encoded with
+    #[structopt(long, value_name = "bytes")]
+    pub ecc: Option<usize>,
 }
 
 #[derive(Debug, StructOpt)]
 pub struct RemoveArgs {
-    /// Chunk type
+    /// Chunk type, `key:<secret>` or `vendor:<secret>` as given to encode
+    #[structopt(required_unless = "trailer")]
+    pub chunk_type: Option<String>,
+    /// Handling of unknown unsafe-to-copy chunks when a critical chunk is removed: keep, warn or drop
+    #[structopt(long, default_value = "drop")]
+    pub unsafe_chunks: CopyPolicy,
+    /// Removes the data stored after IEND
+    #[structopt(long, conflicts_with = "chunk-type")]
+    pub trailer: bool,
+}
+
+#[derive(Debug, Default, StructOpt)]
+pub struct PrintArgs {
+    /// Adds entropy, the most common bytes and the printable share of every
+    /// chunk, which sets encrypted or compressed payloads apart
+    #[structopt(short, long)]
+    pub stats: bool,
+    /// Dump the chunks as JSON instead of a human readable listing
+    #[cfg(feature = "serde")]
+    #[structopt(long, conflicts_with = "stats")]
+    pub json: bool,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, StructOpt)]
+pub struct BuildArgs {
+    /// JSON file produced by `print --json`
+    #[structopt(parse(from_os_str))]
+    pub json: PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct InspectArgs {
+    /// Chunk type or index of the chunk as shown by `print`
+    pub chunk: String,
+    /// Offset of the first byte to dump, decimal or 0x-prefixed hex
+    #[structopt(short, long, default_value = "0", parse(try_from_str = parse_number))]
+    pub offset: usize,
+    /// Number of bytes to dump, everything after the offset by default
+    #[structopt(short = "n", long, parse(try_from_str = parse_number))]
+    pub length: Option<usize>,
+    /// Decodes the data of known chunk types
+    #[structopt(short, long)]
+    pub decode: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TextArgs {
+    #[structopt(subcommand)]
+    pub command: TextCommand,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum TextCommand {
+    /// Lists all text chunks
+    List,
+    /// Adds a new text chunk before IEND
+    Add(TextEntryArgs),
+    /// Replaces the text stored under an existing keyword
+    Edit(TextEntryArgs),
+    /// Removes every text chunk with the keyword
+    Remove {
+        /// Keyword of the text chunks to remove
+        keyword: String,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TextEntryArgs {
+    /// Keyword, e.g. Title, Author, Description or Comment
+    pub keyword: String,
+    /// Text to store
+    pub text: String,
+    /// Compresses the text (zTXt, or compressed iTXt)
+    #[structopt(short = "z", long)]
+    pub compress: bool,
+    /// Stores UTF-8 text in an iTXt chunk; implied for non Latin-1 text
+    #[structopt(short, long)]
+    pub international: bool,
+    /// Language tag of the text, implies --international
+    #[structopt(long)]
+    pub language: Option<String>,
+    /// Keyword translated into the language of the text, implies --international
+    #[structopt(long)]
+    pub translated_keyword: Option<String>,
+}
+
+#[derive(Debug, Default, StructOpt)]
+pub struct MetaArgs {
+    /// Sets the resolution in dots per inch (pHYs)
+    #[structopt(long)]
+    pub dpi: Option<f64>,
+    /// Sets the image gamma (gAMA), e.g. 0.45455
+    #[structopt(long)]
+    pub gamma: Option<f64>,
+    /// Sets the chromaticities (cHRM) as wx,wy,rx,ry,gx,gy,bx,by
+    #[structopt(long)]
+    pub chrm: Option<Chromaticities>,
+    /// Sets the sRGB rendering intent: perceptual, relative, saturation or absolute
+    #[structopt(long)]
+    pub srgb: Option<Srgb>,
+    /// Sets the modification time (tIME), `now` or YYYY-MM-DDTHH:MM:SS in UTC
+    #[structopt(long)]
+    pub time: Option<Time>,
+    /// Embeds the ICC profile (iCCP) read from a file
+    #[structopt(long, parse(from_os_str))]
+    pub iccp: Option<PathBuf>,
+    /// Name of the embedded ICC profile
+    #[structopt(long, default_value = "ICC profile")]
+    pub iccp_name: String,
+    /// Removes the metadata chunk of the given type, can be repeated
+    #[structopt(long, number_of_values = 1)]
+    pub remove: Vec<String>,
+}
+
+#[derive(Debug, Default, StructOpt)]
+pub struct ExifArgs {
+    /// Removes the GPS directory
+    #[structopt(long)]
+    pub redact_gps: bool,
+    /// Removes serial numbers, unique ids and the camera owner name
+    #[structopt(long)]
+    pub redact_serials: bool,
+    /// Removes a tag by name (e.g. Make) or number, can be repeated
+    #[structopt(long, number_of_values = 1)]
+    pub redact: Vec<String>,
+    /// Removes the whole eXIf chunk
+    #[structopt(long)]
+    pub strip: bool,
+}
+
+#[derive(Debug, Default, StructOpt)]
+pub struct StripArgs {
+    /// Chunk type to keep, e.g. sRGB or gAMA, can be repeated
+    #[structopt(short, long, number_of_values = 1)]
+    pub keep: Vec<String>,
+    /// Only reports what would be removed
+    #[structopt(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct PolyglotArgs {
+    /// Files to put into the archive
+    #[structopt(required = true, parse(from_os_str))]
+    pub files: Vec<PathBuf>,
+    /// Stores the archive in an ancillary chunk of this type instead of after IEND
+    #[structopt(short, long)]
+    pub chunk: Option<String>,
+}
+
+#[derive(Debug, Default, StructOpt)]
+pub struct OptimizeArgs {
+    /// Filter strategy to try: none, sub, up, average, paeth or adaptive, can be
+    /// repeated; all of them by default
+    #[structopt(short, long, number_of_values = 1)]
+    pub filter: Vec<Strategy>,
+    /// Deflate level from 0 to 9 to try, can be repeated; 6 and 9 by default
+    #[structopt(short, long, number_of_values = 1)]
+    pub level: Vec<u32>,
+    /// Maximum data size of an IDAT chunk
+    #[structopt(long, default_value = "8192")]
+    pub idat_size: usize,
+    /// Only reports the achievable size
+    #[structopt(long)]
+    pub dry_run: bool,
+    /// Handling of unknown unsafe-to-copy chunks once the image data changed: keep, warn or drop
+    #[structopt(long, default_value = "drop")]
+    pub unsafe_chunks: CopyPolicy,
+}
+
+#[derive(Debug, Default, StructOpt)]
+pub struct DetectArgs {}
+
+#[derive(Debug, StructOpt)]
+pub struct DiffArgs {
+    /// PNG file to compare the input with
+    #[structopt(parse(from_os_str))]
+    pub other: PathBuf,
+    /// Also decodes both images and compares their pixels
+    #[structopt(short, long)]
+    pub pixels: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TransplantArgs {
+    /// PNG file to copy the chunks from
+    #[structopt(parse(from_os_str))]
+    pub source: PathBuf,
+    /// Chunk type to copy, can be repeated
+    #[structopt(short = "t", long = "type", number_of_values = 1)]
+    pub types: Vec<String>,
+    /// Copies the chunk types matching a pattern, where `?` stands for one
+    /// letter and `*` for any number of them, e.g. `pr*`; can be repeated
+    #[structopt(long, number_of_values = 1)]
+    pub pattern: Vec<String>,
+    /// Copies every private chunk
+    #[structopt(long)]
+    pub private: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct MoveArgs {
+    /// Chunk type or index of the chunk as shown by `print`
+    pub chunk: String,
+    /// Index the chunk moves to, as shown by `print`
+    pub position: usize,
+    /// Allows orders that break the image, e.g. IDAT after IEND
+    #[structopt(short, long)]
+    pub force: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct RenameArgs {
+    /// Chunk type or index of the chunk as shown by `print`
+    pub chunk: String,
+    /// New chunk type
     pub chunk_type: String,
+    /// Allows renaming critical chunks and renaming chunks to critical types
+    #[structopt(short, long)]
+    pub force: bool,
 }
 
 #[derive(Debug, StructOpt)]
-pub struct PrintArgs {}
+pub struct ReplaceDataArgs {
+    /// Chunk type or index of the chunk as shown by `print`
+    pub chunk: String,
+    /// File holding the new data
+    #[structopt(parse(from_os_str))]
+    pub file: PathBuf,
+    /// Handling of unknown unsafe-to-copy chunks when a critical chunk changes: keep, warn or drop
+    #[structopt(long, default_value = "drop")]
+    pub unsafe_chunks: CopyPolicy,
+    /// Allows replacing the data of critical chunks
+    #[structopt(short, long)]
+    pub force: bool,
+}
+
+fn parse_number(s: &str) -> std::result::Result<usize, std::num::ParseIntError> {
+    match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+}
@@ -37,7 +37,7 @@ impl Chunk {
         self.length as usize
     }
 
-    fn data(&self) -> &[u8] {
+    pub fn data(&self) -> &[u8] {
         &self.chunk_data
     }
 
@@ -46,10 +46,20 @@ impl Chunk {
     }
 
     // TODO(#2): Make my own implementation of crc hashing
-    fn crc(&self) -> u32 {
+    pub fn crc(&self) -> u32 {
         self.crc
     }
 
+    /// Changes the chunk type, recomputing the CRC.
+    pub fn set_chunk_type(&mut self, chunk_type: ChunkType) {
+        *self = Chunk::new(chunk_type, std::mem::take(&mut self.chunk_data));
+    }
+
+    /// Replaces the data, recomputing the length and the CRC.
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        *self = Chunk::new(self.chunk_type.clone(), data);
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         [
             self.length.to_be_bytes().as_ref(),
@@ -108,6 +118,78 @@ impl fmt::Display for Chunk {
     }
 }
 
+// Chunk data is written as a hex string so that a dumped file stays readable
+// and editable by hand. Length and CRC are informational only: they are
+// recomputed from the type and data when a chunk is deserialized.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Chunk {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Chunk", 4)?;
+        state.serialize_field("type", self.chunk_type())?;
+        state.serialize_field("length", &self.length)?;
+        state.serialize_field("data", &hex::encode(self.data()))?;
+        state.serialize_field("crc", &self.crc())?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Chunk {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error as _;
+
+        #[derive(serde::Deserialize)]
+        struct RawChunk {
+            #[serde(rename = "type")]
+            chunk_type: ChunkType,
+            data: String,
+        }
+
+        let raw = RawChunk::deserialize(deserializer)?;
+        let data = hex::decode(&raw.data).map_err(D::Error::custom)?;
+        Ok(Chunk::new(raw.chunk_type, data))
+    }
+}
+
+/// Hex string helpers shared by everything that serializes raw bytes, usable
+/// with `#[serde(with = "crate::chunk::hex")]`.
+#[cfg(feature = "serde")]
+pub(crate) mod hex {
+    use serde::{de::Error as _, Deserialize};
+
+    pub fn encode(data: &[u8]) -> String {
+        data.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn decode(hex: &str) -> Result<Vec<u8>, &'static str> {
+        if !hex.is_ascii() || hex.len() % 2 != 0 {
+            return Err("chunk data is not a valid hex string");
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| "chunk data is not a valid hex string")
+    }
+
+    pub fn serialize<S: serde::Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode(data))
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        decode(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,6 +230,19 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_chunk_setters() {
+        let mut chunk = testing_chunk();
+        chunk.set_chunk_type(ChunkType::from_str("prVx").unwrap());
+        assert_eq!(chunk.chunk_type().to_string(), "prVx");
+        assert_eq!(chunk.data(), testing_chunk().data());
+        chunk.set_data(b"Other".to_vec());
+        assert_eq!(chunk.length(), 5);
+        let expected = Chunk::new(ChunkType::from_str("prVx").unwrap(), b"Other".to_vec());
+        assert_eq!(chunk.crc(), expected.crc());
+        assert!(Chunk::try_from(chunk.as_bytes().as_ref()).is_ok());
+    }
+
     #[test]
     fn test_valid_chunk_from_bytes() {
         let data_length: u32 = 42;
@@ -195,4 +290,23 @@ mod tests {
 
         assert!(chunk.is_err());
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_chunk_serde_roundtrip() {
+        let chunk = testing_chunk();
+        let json = serde_json::to_string(&chunk).unwrap();
+        let actual: Chunk = serde_json::from_str(&json).unwrap();
+        assert_eq!(actual.as_bytes(), chunk.as_bytes());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_chunk_deserialize_recomputes_crc() {
+        let json = r#"{"type": "RuSt", "data": "4869", "crc": 1}"#;
+        let chunk: Chunk = serde_json::from_str(json).unwrap();
+        assert_eq!(chunk.data_as_string().unwrap(), "Hi");
+        assert_eq!(chunk.length(), 2);
+        assert_ne!(chunk.crc(), 1);
+    }
 }
@@ -9,36 +9,131 @@ use crate::BYTE_SIZE;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ChunkType([u8; BYTE_SIZE]);
 
+/// The four property bits encoded in the case of the chunk type letters.
+/// A flag is set when the corresponding letter is lowercase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Properties {
+    /// First letter: the chunk is not necessary to display the image.
+    pub ancillary: bool,
+    /// Second letter: the type is not registered with the PNG specification.
+    pub private: bool,
+    /// Third letter: reserved, must be unset in the current PNG version.
+    pub reserved: bool,
+    /// Fourth letter: editors may copy the chunk even if they do not know it.
+    pub safe_to_copy: bool,
+}
+
 impl ChunkType {
+    const PROPERTY_BIT: u8 = 1 << 5;
+
     pub fn bytes(&self) -> &[u8; 4] {
         &self.0
     }
 
-    fn at_char(&self, index: usize) -> char {
-        (self.0)[index] as char
+    fn property_bit(&self, index: usize) -> bool {
+        (self.0)[index] & Self::PROPERTY_BIT != 0
+    }
+
+    pub fn properties(&self) -> Properties {
+        Properties {
+            ancillary: self.property_bit(0),
+            private: self.property_bit(1),
+            reserved: self.property_bit(2),
+            safe_to_copy: self.property_bit(3),
+        }
     }
 
     pub fn is_critical(&self) -> bool {
-        self.at_char(0).is_uppercase()
+        !self.properties().ancillary
     }
 
     pub fn is_public(&self) -> bool {
-        self.at_char(1).is_uppercase()
+        !self.properties().private
     }
 
     pub fn is_reserved_bit_valid(&self) -> bool {
-        self.at_char(2).is_uppercase()
+        !self.properties().reserved
     }
 
     pub fn is_safe_to_copy(&self) -> bool {
-        !self.at_char(3).is_uppercase()
+        self.properties().safe_to_copy
     }
 
+    /// A chunk type is valid when all four bytes are ASCII letters and the
+    /// reserved bit is unset. Every other combination of properties is allowed.
     pub fn is_valid(&self) -> bool {
-        !self.is_public() && self.is_reserved_bit_valid()
+        self.0.iter().all(u8::is_ascii_alphabetic) && self.is_reserved_bit_valid()
+    }
+
+    /// Starts building a chunk type named after `name`, whose letter case is
+    /// replaced by the requested properties.
+    pub fn builder(name: &str) -> ChunkTypeBuilder {
+        ChunkTypeBuilder {
+            name: name.to_string(),
+            properties: Properties::default(),
+        }
+    }
+
+    /// Whether the type is defined by the PNG specification or one of its
+    /// registered extensions.
+    pub fn is_standard(&self) -> bool {
+        STANDARD_TYPES.contains(&self.bytes())
+    }
+}
+
+/// Builds a [`ChunkType`] from a name and the desired property bits. By
+/// default the type is critical, public and unsafe to copy.
+#[derive(Debug, Clone)]
+pub struct ChunkTypeBuilder {
+    name: String,
+    properties: Properties,
+}
+
+impl ChunkTypeBuilder {
+    pub fn ancillary(mut self, ancillary: bool) -> Self {
+        self.properties.ancillary = ancillary;
+        self
+    }
+
+    pub fn private(mut self, private: bool) -> Self {
+        self.properties.private = private;
+        self
+    }
+
+    pub fn safe_to_copy(mut self, safe_to_copy: bool) -> Self {
+        self.properties.safe_to_copy = safe_to_copy;
+        self
+    }
+
+    pub fn build(self) -> Result<ChunkType> {
+        let chunk_type = ChunkType::from_str(&self.name)?;
+        let Properties {
+            ancillary,
+            private,
+            reserved,
+            safe_to_copy,
+        } = self.properties;
+        let mut bytes = *chunk_type.bytes();
+        for (byte, &flag) in bytes
+            .iter_mut()
+            .zip(&[ancillary, private, reserved, safe_to_copy])
+        {
+            if flag {
+                *byte |= ChunkType::PROPERTY_BIT;
+            } else {
+                *byte &= !ChunkType::PROPERTY_BIT;
+            }
+        }
+        Ok(ChunkType(bytes))
     }
 }
 
+const STANDARD_TYPES: [&[u8; BYTE_SIZE]; 30] = [
+    b"IHDR", b"PLTE", b"IDAT", b"IEND", b"tRNS", b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB",
+    b"cICP", b"mDCv", b"cLLi", b"tEXt", b"zTXt", b"iTXt", b"bKGD", b"hIST", b"pHYs", b"sPLT",
+    b"eXIf", b"tIME", b"acTL", b"fcTL", b"fdAT", b"oFFs", b"pCAL", b"sCAL", b"gIFg", b"sTER",
+];
+
 impl TryFrom<[u8; 4]> for ChunkType {
     type Error = Error<'static>;
 
@@ -61,8 +156,8 @@ impl FromStr for ChunkType {
     type Err = Error<'static>;
 
     fn from_str(s: &str) -> Result<Self> {
-        if s.len() > BYTE_SIZE {
-            return Err(Error::Custom("Too long lenght of chunk type"));
+        if s.len() != BYTE_SIZE {
+            return Err(Error::Custom("Chunk type must be exactly four letters"));
         }
         if !s.chars().all(|c| c.is_ascii_alphabetic()) {
             return Err(Error::Custom("Invalid assii literal"));
@@ -73,6 +168,26 @@ impl FromStr for ChunkType {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChunkType {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ChunkType {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        ChunkType::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,6 +263,47 @@ mod tests {
         assert!(chunk.is_valid());
     }
 
+    #[test]
+    pub fn test_public_chunk_is_valid() {
+        for name in &["IHDR", "IDAT", "IEND", "tEXt", "gAMA", "pHYs"] {
+            assert!(ChunkType::from_str(name).unwrap().is_valid(), "{}", name);
+        }
+    }
+
+    #[test]
+    pub fn test_chunk_type_properties() {
+        let properties = ChunkType::from_str("tEXt").unwrap().properties();
+        assert_eq!(
+            properties,
+            Properties {
+                ancillary: true,
+                private: false,
+                reserved: false,
+                safe_to_copy: true,
+            }
+        );
+        let properties = ChunkType::from_str("IHDR").unwrap().properties();
+        assert_eq!(properties, Properties::default());
+    }
+
+    #[test]
+    pub fn test_chunk_type_builder() {
+        let chunk = ChunkType::builder("RUST")
+            .ancillary(true)
+            .private(true)
+            .safe_to_copy(true)
+            .build()
+            .unwrap();
+        assert_eq!(&chunk.to_string(), "ruSt");
+        assert!(chunk.is_valid());
+
+        let chunk = ChunkType::builder("rust").build().unwrap();
+        assert_eq!(&chunk.to_string(), "RUST");
+        assert!(chunk.is_critical() && chunk.is_public() && !chunk.is_safe_to_copy());
+
+        assert!(ChunkType::builder("ru5t").build().is_err());
+    }
+
     #[test]
     pub fn test_invalid_chunk_is_valid() {
         let chunk = ChunkType::from_str("Rust").unwrap();
@@ -155,6 +311,16 @@ mod tests {
 
         let chunk = ChunkType::from_str("Ru1t");
         assert!(chunk.is_err());
+
+        let chunk = ChunkType::from_str("Ru");
+        assert!(chunk.is_err());
+    }
+
+    #[test]
+    pub fn test_chunk_type_is_standard() {
+        assert!(ChunkType::from_str("IDAT").unwrap().is_standard());
+        assert!(ChunkType::from_str("tEXt").unwrap().is_standard());
+        assert!(!ChunkType::from_str("RuSt").unwrap().is_standard());
     }
 
     #[test]
@@ -0,0 +1,82 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use sha2::Sha256;
+
+use crate::{Error, Result};
+
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const ROUNDS: u32 = 100_000;
+
+fn derive_key(password: &str, salt: &[u8]) -> Key {
+    let mut key = Key::default();
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, ROUNDS, &mut key);
+    key
+}
+
+/// Encrypts `plaintext` with a key derived from `password`. The random salt
+/// and nonce are stored in front of the ciphertext.
+pub fn encrypt(password: &str, plaintext: &[u8]) -> Vec<u8> {
+    let mut salt = [0; SALT_SIZE];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(&derive_key(password, &salt))
+        .encrypt(&nonce, plaintext)
+        .expect("encrypting into a vector does not fail");
+
+    let mut sealed = Vec::with_capacity(SALT_SIZE + NONCE_SIZE + ciphertext.len());
+    sealed.extend_from_slice(&salt);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend(ciphertext);
+    sealed
+}
+
+/// Reverses [`encrypt`], failing on a wrong password or tampered data.
+pub fn decrypt(password: &str, sealed: &[u8]) -> Result<Vec<u8>> {
+    if sealed.len() < SALT_SIZE + NONCE_SIZE {
+        return Err(Error::Custom("Encrypted payload is too short"));
+    }
+    let (salt, rest) = sealed.split_at(SALT_SIZE);
+    let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+    ChaCha20Poly1305::new(&derive_key(password, salt))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| Error::Custom("Unable to decrypt the payload, wrong password?"))
+}
+
+/// Encodes binary data as base64 so it can live in text metadata.
+pub fn to_text(data: &[u8]) -> String {
+    STANDARD.encode(data)
+}
+
+pub fn from_text(text: &str) -> Result<Vec<u8>> {
+    STANDARD
+        .decode(text.trim())
+        .map_err(|_| Error::Custom("Payload is not valid base64"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let sealed = encrypt("hunter2", b"Message");
+        assert_eq!(sealed.len(), SALT_SIZE + NONCE_SIZE + 7 + 16);
+        assert_eq!(decrypt("hunter2", &sealed).unwrap(), b"Message");
+        assert!(decrypt("hunter3", &sealed).is_err());
+        assert!(decrypt("hunter2", &sealed[..10]).is_err());
+        // Fresh salt and nonce every time.
+        assert_ne!(sealed, encrypt("hunter2", b"Message"));
+    }
+
+    #[test]
+    fn test_text() {
+        let text = to_text(&[0, 1, 2, 255]);
+        assert_eq!(text, "AAEC/w==");
+        assert_eq!(from_text(&text).unwrap(), vec![0, 1, 2, 255]);
+        assert!(from_text("not base64!").is_err());
+    }
+}
@@ -1,9 +1,30 @@
+#[cfg(feature = "serde")]
+use crate::args::BuildArgs;
 use crate::{
-    args::{DecodeArgs, EncodeArgs, RemoveArgs},
-    Chunk, ChunkType, Error, Png, Result,
+    args::{
+        DecodeArgs, DetectArgs, DiffArgs, EncodeArgs, ExifArgs, InspectArgs, MetaArgs, MoveArgs,
+        OptimizeArgs, PolyglotArgs, PrintArgs, RemoveArgs, RenameArgs, ReplaceDataArgs, StripArgs,
+        TextArgs, TextCommand, TextEntryArgs, TransplantArgs,
+    },
+    cipher::{decrypt, encrypt},
+    cover::{cover_text, uncover_text},
+    detect::analyze,
+    diff::{compare, compare_pixels},
+    disguise::{resolve_chunk_type, resolve_new_chunk_type},
+    ecc,
+    exif::Exif,
+    filter::{filter, unfilter, Strategy},
+    inspect::{describe, hexdump},
+    lsb::{self, Selection},
+    meta::{Chromaticities, Gamma, Iccp, Metadata, PhysicalDimensions, Srgb, Time},
+    palette::{self, Layout},
+    png::CopyPolicy,
+    policy::{check_payload_type, suggest_payload_type},
+    stats::ByteStats,
+    zip, zlib, Chunk, ChunkType, Error, Png, Result, TextChunk, TextKind, BYTE_SIZE,
 };
 use std::{
-    convert::TryInto,
+    convert::{TryFrom, TryInto},
     fs::OpenOptions,
     io::{Read, Write},
     path::Path,
@@ -15,28 +36,239 @@ fn take_png<P: AsRef<Path>>(input: P) -> Result<Png> {
     let mut buffer = Vec::with_capacity(1_000_000);
 
     file.read_to_end(&mut buffer)?;
-    Ok(buffer.as_slice().try_into()?)
+    buffer.as_slice().try_into()
+}
+
+/// Encrypts the message when a password is given, then adds `ecc` parity
+/// bytes per block for error correction.
+fn seal(message: String, password: Option<&str>, ecc: Option<usize>) -> Result<Vec<u8>> {
+    let data = match password {
+        Some(password) => encrypt(password, message.as_bytes()),
+        None => message.into_bytes(),
+    };
+    match ecc {
+        Some(parity) => ecc::protect(&data, parity),
+        None => Ok(data),
+    }
+}
+
+/// Reverses [`seal`], reporting how many bytes error correction repaired.
+fn unseal(data: &[u8], password: Option<&str>, ecc: Option<usize>) -> Result<String> {
+    let (data, corrected) = match ecc {
+        Some(parity) => {
+            let (data, corrected) = ecc::recover(data, parity)?;
+            (data, Some(corrected))
+        }
+        None => (data.to_vec(), None),
+    };
+    let message = match password {
+        Some(password) => String::from_utf8(decrypt(password, &data)?)?,
+        None => String::from_utf8(data)?,
+    };
+    if let Some(corrected) = corrected {
+        println!("Corrected {} damaged bytes", corrected);
+    }
+    Ok(message)
+}
+
+/// Pixel bits a chunk type spec of encode and decode refers to: `lsb`,
+/// `alpha` or `alpha:lsb`.
+fn pixel_selection(spec: &str) -> Option<Selection> {
+    match spec {
+        "lsb" => Some(Selection::Color),
+        "alpha" => Some(Selection::Transparent { alpha: false }),
+        "alpha:lsb" => Some(Selection::Transparent { alpha: true }),
+        _ => None,
+    }
+}
+
+/// Matrix embedding only exists for messages hidden in the pixels.
+fn check_matrix_spec(spec: Option<&str>, matrix: u32) -> Result<()> {
+    let in_pixels = spec.is_some_and(|spec| pixel_selection(spec).is_some() || spec == "palette");
+    if matrix != 1 && !in_pixels {
+        return Err(Error::Custom(
+            "--matrix only applies to lsb, alpha and palette",
+        ));
+    }
+    Ok(())
+}
+
+/// A damaged chunk fails its CRC check before the payload is read, so error
+/// correction only helps messages in the pixels or after IEND.
+fn check_ecc_spec(spec: Option<&str>, ecc: Option<usize>) -> Result<()> {
+    let correctable = match spec {
+        Some(spec) => pixel_selection(spec).is_some() || spec == "palette",
+        None => true,
+    };
+    if ecc.is_some() && !correctable {
+        return Err(Error::Custom(
+            "--ecc only applies to lsb, alpha, palette and --trailer",
+        ));
+    }
+    Ok(())
 }
 
 pub fn encode<S: AsRef<Path>>(input: S, args: EncodeArgs) -> Result<()> {
+    check_matrix_spec(args.chunk_type.as_deref(), args.matrix)?;
+    check_ecc_spec(args.chunk_type.as_deref(), args.ecc)?;
     let mut png = take_png(&input)?;
-    png.append_chunk(Chunk::new(
-        ChunkType::from_str(&args.chunk_type)?,
-        args.message.into_bytes(),
-    ));
+    let password = args.password.as_deref();
+    if let Some(message) = args.trailer {
+        if !png.trailer().is_empty() {
+            println!("Replaced {} bytes after IEND", png.trailer().len());
+        }
+        png.set_trailer(seal(message, password, args.ecc)?);
+        return write_png(input, &png);
+    }
+    let spec = args.chunk_type.ok_or(Error::Custom("Missing chunk type"))?;
+    let message = args.message.ok_or(Error::Custom("Missing message"))?;
 
-    let mut file = std::fs::File::create(input)?;
-    file.write_all(&png.as_bytes())?;
-    Ok(())
+    if let Some(keyword) = spec.strip_prefix("text:") {
+        let password = password.ok_or(Error::Custom(
+            "Hiding a message in a text chunk needs a --password",
+        ))?;
+        let text = cover_text(keyword, &seal(message, Some(password), args.ecc)?);
+        png.add_text(&text)?;
+        println!(
+            "Encoded the message into the {} chunk with the keyword {}",
+            text.kind.chunk_type(),
+            keyword
+        );
+        return write_png(input, &png);
+    }
+
+    if let Some(selection) = pixel_selection(&spec) {
+        let mut image = png.stored_image()?;
+        let payload = seal(message, password, args.ecc)?;
+        let capacity = lsb::capacity(&image, selection, args.matrix)?;
+        if payload.len() > capacity {
+            eprintln!(
+                "The message takes {} bytes, the pixels hold {} with --matrix {}",
+                payload.len(),
+                capacity,
+                args.matrix
+            );
+            return Err(Error::Custom("The message does not fit into the image"));
+        }
+        let changed = lsb::embed(&mut image, &payload, selection, args.matrix)?;
+        let interlaced = png.ihdr()?.is_interlaced() && !args.deinterlace;
+        png.set_image(&image, interlaced)?;
+        apply_copy_policy(&mut png, args.unsafe_chunks);
+        match selection {
+            Selection::Color => {
+                println!("Encoded the message into the least significant bits of the pixels")
+            }
+            Selection::Transparent { .. } => {
+                println!("Encoded the message into the transparent pixels")
+            }
+        }
+        println!(
+            "Used {} of {} available bytes, changed {} bits",
+            payload.len(),
+            capacity,
+            changed
+        );
+        return write_png(input, &png);
+    }
+
+    if spec == "palette" {
+        let interlaced = png.ihdr()?.is_interlaced() && !args.deinterlace;
+        let payload = seal(message, password, args.ecc)?;
+        let layout = palette::embed(&mut png, &payload, interlaced, args.matrix)?;
+        apply_copy_policy(&mut png, args.unsafe_chunks);
+        if layout == Layout::Nearest {
+            println!(
+                "The palette is too full to duplicate, some pixels change to the nearest color"
+            );
+        }
+        println!("Encoded the message into the parity of the palette indices");
+        return write_png(input, &png);
+    }
+
+    let chunk_type = resolve_new_chunk_type(&spec, &png)?;
+    if !args.force {
+        if let Err(violation) = check_payload_type(&chunk_type) {
+            eprintln!(
+                "{} would break the image or clash with other software, try {} or pass --force",
+                chunk_type,
+                suggest_payload_type(&chunk_type)
+            );
+            return Err(Error::Custom(violation.message()));
+        }
+    }
+    if chunk_type.to_string() != spec {
+        println!("Encoded the message into the chunk {}", chunk_type);
+    }
+    png.insert_before_end(Chunk::new(chunk_type, seal(message, password, args.ecc)?));
+
+    write_png(input, &png)
 }
 
 pub fn decode<S: AsRef<Path>>(input: S, args: DecodeArgs) -> Result<()> {
+    check_matrix_spec(args.chunk_type.as_deref(), args.matrix)?;
+    check_ecc_spec(args.chunk_type.as_deref(), args.ecc)?;
     let png = take_png(&input)?;
-    if let Some(chunk) = png.chunk_by_type(&args.chunk_type) {
+    let password = args.password.as_deref();
+    let ecc = args.ecc;
+    if args.trailer {
+        if png.trailer().is_empty() {
+            return Err(Error::Custom("There is no data after IEND"));
+        }
+        println!(
+            "Hidden message after IEND: '{}'",
+            unseal(png.trailer(), password, ecc)?
+        );
+        return Ok(());
+    }
+    let spec = args.chunk_type.ok_or(Error::Custom("Missing chunk type"))?;
+
+    if let Some(selection) = pixel_selection(&spec) {
+        let payload = lsb::extract(&png.stored_image()?, selection, args.matrix)?;
+        let place = match selection {
+            Selection::Color => "pixels",
+            Selection::Transparent { .. } => "transparent pixels",
+        };
+        println!(
+            "Hidden message in the {}: '{}'",
+            place,
+            unseal(&payload, password, ecc)?
+        );
+        return Ok(());
+    }
+
+    if spec == "palette" {
+        let payload = palette::extract(&png, args.matrix)?;
+        println!(
+            "Hidden message in the palette indices: '{}'",
+            unseal(&payload, password, ecc)?
+        );
+        return Ok(());
+    }
+
+    if let Some(keyword) = spec.strip_prefix("text:") {
+        let password = password.ok_or(Error::Custom(
+            "Decoding a message from a text chunk needs a --password",
+        ))?;
+        let message = png
+            .text_chunks()?
+            .iter()
+            .filter(|text| text.keyword == keyword)
+            .filter_map(uncover_text)
+            .find_map(|payload| unseal(&payload, Some(password), ecc).ok())
+            .ok_or(Error::Custom("Unable to decode text chunk"))?;
+        println!(
+            "Hidden message in the text chunk {}: '{}'",
+            keyword, message
+        );
+        return Ok(());
+    }
+
+    let chunk_type = resolve_chunk_type(&spec)?;
+    if let Some(chunk) = png.chunk_by_type(&chunk_type.to_string()) {
         println!(
             "Hidden message in the chunk {}: '{}'",
-            chunk.chunk_type().to_string(),
-            chunk.data_as_string()?
+            chunk.chunk_type(),
+            unseal(chunk.data(), password, ecc)?
         );
     } else {
         return Err(Error::Custom("Unable to decode chunk"));
@@ -44,28 +276,611 @@ pub fn decode<S: AsRef<Path>>(input: S, args: DecodeArgs) -> Result<()> {
     Ok(())
 }
 
+/// Whether changing a chunk of this type changes the image itself, after
+/// which chunks that are not safe to copy may no longer match it. Critical
+/// chunks the standard does not define, like payload chunks, do not count.
+fn is_image_chunk(chunk_type: &ChunkType) -> bool {
+    chunk_type.is_critical() && chunk_type.is_standard()
+}
+
+/// Reports what [`Png::apply_copy_policy`] found after critical chunks changed.
+fn apply_copy_policy(png: &mut Png, policy: CopyPolicy) {
+    for chunk_type in png.apply_copy_policy(policy) {
+        match policy {
+            CopyPolicy::Drop => println!("Dropped {}, it is not safe to copy", chunk_type),
+            CopyPolicy::Warn => eprintln!(
+                "Warning: {} is not safe to copy and may no longer match the image",
+                chunk_type
+            ),
+            CopyPolicy::Keep => {}
+        }
+    }
+}
+
 pub fn remove<S: AsRef<Path>>(input: S, args: RemoveArgs) -> Result<()> {
     let mut png = take_png(&input)?;
-    png.remove_chunk(&args.chunk_type)?;
+    if args.trailer {
+        if png.trailer().is_empty() {
+            return Err(Error::Custom("There is no data after IEND"));
+        }
+        png.set_trailer(Vec::new());
+        return write_png(input, &png);
+    }
+    let spec = args.chunk_type.ok_or(Error::Custom("Missing chunk type"))?;
+    let chunk_type = resolve_chunk_type(&spec)?;
+    let chunk = png.remove_chunk(&chunk_type.to_string())?;
+    if is_image_chunk(chunk.chunk_type()) {
+        apply_copy_policy(&mut png, args.unsafe_chunks);
+    }
 
     let mut file = std::fs::File::create(input)?;
     file.write_all(&png.as_bytes())?;
     Ok(())
 }
 
-pub fn print(input: &Path) -> Result<()> {
-    let png = take_png(&input)?;
+#[cfg_attr(not(feature = "serde"), allow(unused_variables))]
+pub fn print<S: AsRef<Path>>(input: S, args: PrintArgs) -> Result<()> {
+    let input = input.as_ref();
+    let png = take_png(input)?;
+    #[cfg(feature = "serde")]
+    {
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&png)?);
+            return Ok(());
+        }
+    }
     println!("File: {}, Size: {}", input.display(), png.as_bytes().len());
     for (i, chunk) in png.chunks().iter().enumerate() {
         print!("\n({})", i + 1);
         print!("{}", chunk);
+        if let Ok(exif) = Exif::from_chunk(chunk) {
+            println!("  Exif: {}", exif);
+        }
+        if args.stats && !chunk.data().is_empty() {
+            print!("{}", ByteStats::new(chunk.data()));
+        }
+    }
+    if !png.trailer().is_empty() {
+        println!("\nTrailer: {} bytes after IEND", png.trailer().len());
+        if args.stats {
+            print!("{}", ByteStats::new(png.trailer()));
+        }
+    }
+    Ok(())
+}
+
+/// Looks a chunk up by its 1-based index as shown by `print`, or by type.
+fn select_chunk<'a>(png: &'a Png, chunk: &str) -> Result<(usize, &'a Chunk)> {
+    let found = match chunk.parse::<usize>() {
+        Ok(index) => index
+            .checked_sub(1)
+            .and_then(|i| png.chunks().get(i).map(|chunk| (i, chunk))),
+        Err(_) => png
+            .chunks()
+            .iter()
+            .enumerate()
+            .find(|(_, c)| c.chunk_type().to_string() == chunk),
+    };
+    found.ok_or(Error::Custom("Unable to find chunk"))
+}
+
+pub fn inspect<S: AsRef<Path>>(input: S, args: InspectArgs) -> Result<()> {
+    let png = take_png(&input)?;
+    let (index, chunk) = select_chunk(&png, &args.chunk)?;
+    let data = chunk.data();
+    if args.offset > data.len() {
+        return Err(Error::Custom("Offset is past the end of the chunk data"));
+    }
+    let end = match args.length {
+        Some(length) => data.len().min(args.offset.saturating_add(length)),
+        None => data.len(),
+    };
+
+    print!("({}){}", index + 1, chunk);
+    if args.decode {
+        if let Some(description) = describe(chunk) {
+            print!("{}", description);
+        }
+    }
+    println!();
+    print!("{}", hexdump(&data[args.offset..end], args.offset));
+    Ok(())
+}
+
+fn write_png<P: AsRef<Path>>(output: P, png: &Png) -> Result<()> {
+    let mut file = std::fs::File::create(output)?;
+    file.write_all(&png.as_bytes())?;
+    Ok(())
+}
+
+/// Applies the command line options on top of `text`, picking iTXt whenever
+/// the requested fields cannot be stored in a Latin-1 chunk.
+fn apply_text_entry(mut text: TextChunk, args: TextEntryArgs) -> TextChunk {
+    text.text = args.text;
+    if let Some(language) = args.language {
+        text.language_tag = language;
+    }
+    if let Some(translated) = args.translated_keyword {
+        text.translated_keyword = translated;
+    }
+    let international = args.international
+        || !text.language_tag.is_empty()
+        || !text.translated_keyword.is_empty()
+        || !TextChunk::is_latin1(&text.text)
+        || matches!(text.kind, TextKind::International { .. });
+    let compressed = args.compress
+        || matches!(
+            text.kind,
+            TextKind::Compressed | TextKind::International { compressed: true }
+        );
+    text.kind = match (international, compressed) {
+        (true, compressed) => TextKind::International { compressed },
+        (false, true) => TextKind::Compressed,
+        (false, false) => TextKind::Plain,
+    };
+    text
+}
+
+/// Decodes every text chunk like [`Png::text_chunks`], but skips the
+/// malformed ones with a warning instead of failing.
+fn readable_text_chunks(png: &Png) -> Vec<TextChunk> {
+    let mut texts = Vec::new();
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        if !TextChunk::is_text_chunk(chunk) {
+            continue;
+        }
+        match TextChunk::try_from(chunk) {
+            Ok(text) => texts.push(text),
+            Err(e) => eprintln!(
+                "Warning: skipped ({}) {}, {}",
+                index + 1,
+                chunk.chunk_type(),
+                e
+            ),
+        }
+    }
+    texts
+}
+
+pub fn text<S: AsRef<Path>>(input: S, args: TextArgs) -> Result<()> {
+    let mut png = take_png(&input)?;
+    match args.command {
+        TextCommand::List => {
+            for text in readable_text_chunks(&png) {
+                println!("{}", text);
+            }
+            return Ok(());
+        }
+        TextCommand::Add(args) => {
+            let text = TextChunk::new(TextKind::Plain, &args.keyword, "");
+            png.add_text(&apply_text_entry(text, args))?;
+        }
+        TextCommand::Edit(args) => {
+            let text = readable_text_chunks(&png)
+                .into_iter()
+                .find(|text| text.keyword == args.keyword)
+                .ok_or(Error::Custom("Unable to find text chunk"))?;
+            png.replace_text(&apply_text_entry(text, args))?;
+        }
+        TextCommand::Remove { keyword } => {
+            png.remove_text(&keyword)?;
+        }
+    }
+    write_png(input, &png)
+}
+
+fn print_meta<T: Metadata>(png: &Png) -> Result<()> {
+    if let Some(value) = png.meta::<T>()? {
+        println!("{}: {}", T::CHUNK_TYPE, value);
+    }
+    Ok(())
+}
+
+pub fn meta<S: AsRef<Path>>(input: S, args: MetaArgs) -> Result<()> {
+    let mut png = take_png(&input)?;
+    let mut modified = false;
+
+    let managed = [
+        Time::CHUNK_TYPE,
+        PhysicalDimensions::CHUNK_TYPE,
+        Gamma::CHUNK_TYPE,
+        Chromaticities::CHUNK_TYPE,
+        Srgb::CHUNK_TYPE,
+        Iccp::CHUNK_TYPE,
+    ];
+    if !args
+        .remove
+        .iter()
+        .all(|chunk_type| managed.contains(&chunk_type.as_str()))
+    {
+        return Err(Error::Custom(
+            "--remove only takes tIME, pHYs, gAMA, cHRM, sRGB and iCCP",
+        ));
+    }
+    for chunk_type in &args.remove {
+        png.remove_chunk(chunk_type)?;
+        modified = true;
+    }
+    if let Some(dpi) = args.dpi {
+        png.set_meta(&PhysicalDimensions::from_dpi(dpi)?)?;
+        modified = true;
+    }
+    if let Some(gamma) = args.gamma {
+        png.set_meta(&Gamma(gamma))?;
+        modified = true;
+    }
+    if let Some(chrm) = args.chrm {
+        png.set_meta(&chrm)?;
+        modified = true;
+    }
+    if let Some(srgb) = args.srgb {
+        png.set_meta(&srgb)?;
+        modified = true;
     }
+    if let Some(time) = args.time {
+        png.set_meta(&time)?;
+        modified = true;
+    }
+    if let Some(path) = args.iccp {
+        let profile = std::fs::read(path)?;
+        png.set_meta(&Iccp {
+            name: args.iccp_name,
+            profile,
+        })?;
+        modified = true;
+    }
+    if modified {
+        write_png(&input, &png)?;
+    }
+
+    print_meta::<Time>(&png)?;
+    print_meta::<PhysicalDimensions>(&png)?;
+    print_meta::<Gamma>(&png)?;
+    print_meta::<Chromaticities>(&png)?;
+    print_meta::<Srgb>(&png)?;
+    print_meta::<Iccp>(&png)?;
+    Ok(())
+}
+
+pub fn exif<S: AsRef<Path>>(input: S, args: ExifArgs) -> Result<()> {
+    let mut png = take_png(&input)?;
+    if args.strip {
+        png.remove_chunk(Exif::CHUNK_TYPE)?;
+        return write_png(input, &png);
+    }
+    let mut exif: Exif = png.meta()?.ok_or(Error::Custom("There is no eXIf chunk"))?;
+
+    let mut removed = 0;
+    if args.redact_gps {
+        removed += exif.redact_gps()?;
+    }
+    if args.redact_serials {
+        removed += exif.redact_serials()?;
+    }
+    for tag in &args.redact {
+        removed += exif.redact(|entry| entry.matches(tag))?;
+    }
+    if removed > 0 {
+        png.set_meta(&exif)?;
+        write_png(&input, &png)?;
+        println!("Redacted {} tags", removed);
+    }
+    println!("{}", exif);
     Ok(())
 }
 
+pub fn strip<S: AsRef<Path>>(input: S, args: StripArgs) -> Result<()> {
+    let mut png = take_png(&input)?;
+    let removed = png.strip(&args.keep)?;
+    for chunk in &removed {
+        println!(
+            "Removed {} ({} bytes)",
+            chunk.chunk_type(),
+            chunk.data().len()
+        );
+    }
+    println!("{} chunks removed", removed.len());
+    let trailer = png.trailer().len();
+    if trailer > 0 {
+        png.set_trailer(Vec::new());
+        println!("Removed {} bytes after IEND", trailer);
+    }
+    if !args.dry_run && (!removed.is_empty() || trailer > 0) {
+        write_png(input, &png)?;
+    }
+    Ok(())
+}
+
+pub fn polyglot<S: AsRef<Path>>(input: S, args: PolyglotArgs) -> Result<()> {
+    let mut png = take_png(&input)?;
+    let mut files = Vec::with_capacity(args.files.len());
+    for path in &args.files {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or(Error::Custom("File names must be valid UTF-8"))?;
+        files.push((name.to_string(), std::fs::read(path)?));
+    }
+
+    match args.chunk {
+        Some(chunk_type) => {
+            let chunk_type = ChunkType::from_str(&chunk_type)?;
+            if let Err(violation) = check_payload_type(&chunk_type) {
+                return Err(Error::Custom(violation.message()));
+            }
+            // The archive starts after the length and type of the new chunk;
+            // its CRC and everything from IEND on follow it.
+            let start = png.end_offset() + 2 * BYTE_SIZE;
+            let trailing = BYTE_SIZE + png.as_bytes().len() - png.end_offset();
+            let archive = zip::archive(&files, start, trailing)?;
+            png.insert_before_end(Chunk::new(chunk_type, archive));
+        }
+        None => {
+            if !png.trailer().is_empty() {
+                println!("Replaced {} bytes after IEND", png.trailer().len());
+            }
+            png.set_trailer(Vec::new());
+            let archive = zip::archive(&files, png.as_bytes().len(), 0)?;
+            png.set_trailer(archive);
+        }
+    }
+    println!("Embedded a zip archive with {} files", files.len());
+    write_png(input, &png)
+}
+
+pub fn optimize<S: AsRef<Path>>(input: S, args: OptimizeArgs) -> Result<()> {
+    let mut png = take_png(&input)?;
+    let ihdr = png.ihdr()?;
+    let raw = unfilter(&png.image_data()?, &ihdr)?;
+    let strategies = if args.filter.is_empty() {
+        Strategy::ALL.to_vec()
+    } else {
+        args.filter
+    };
+    let levels = if args.level.is_empty() {
+        vec![6, 9]
+    } else {
+        args.level
+    };
+    if levels.iter().any(|&level| level > 9) {
+        return Err(Error::Custom("Deflate levels range from 0 to 9"));
+    }
+
+    let mut best: Option<(Vec<u8>, Strategy, u32)> = None;
+    for &strategy in &strategies {
+        let filtered = filter(&raw, &ihdr, strategy);
+        for &level in &levels {
+            let compressed = zlib::compress_with_level(&filtered, level);
+            let smaller = match &best {
+                Some((smallest, _, _)) => compressed.len() < smallest.len(),
+                None => true,
+            };
+            if smaller {
+                best = Some((compressed, strategy, level));
+            }
+        }
+    }
+    let (compressed, strategy, level) = best.expect("at least one strategy and level");
+    let original = png.idat().len();
+    println!(
+        "IDAT: {} -> {} bytes (filter {}, level {})",
+        original,
+        compressed.len(),
+        strategy,
+        level
+    );
+    if compressed.len() >= original {
+        println!("The image data is already smaller, keeping it");
+        return Ok(());
+    }
+    png.replace_idat(&compressed, args.idat_size)?;
+    if args.dry_run {
+        if args.unsafe_chunks == CopyPolicy::Drop {
+            for chunk_type in png.apply_copy_policy(CopyPolicy::Keep) {
+                println!("Would drop {}, it is not safe to copy", chunk_type);
+            }
+        }
+        return Ok(());
+    }
+    apply_copy_policy(&mut png, args.unsafe_chunks);
+    write_png(input, &png)
+}
+
+#[cfg(feature = "serde")]
+pub fn build<S: AsRef<Path>>(input: S, args: BuildArgs) -> Result<()> {
+    let json = std::fs::read_to_string(args.json)?;
+    let png: Png = serde_json::from_str(&json)?;
+
+    let mut file = std::fs::File::create(input)?;
+    file.write_all(&png.as_bytes())?;
+    Ok(())
+}
+
+pub fn detect<S: AsRef<Path>>(input: S, _args: DetectArgs) -> Result<()> {
+    let png = take_png(&input)?;
+    for finding in analyze(&png) {
+        println!("{:<20} {:.2}", finding.method, finding.score);
+        for note in &finding.notes {
+            println!("  {}", note);
+        }
+    }
+    Ok(())
+}
+
+pub fn diff<S: AsRef<Path>>(input: S, args: DiffArgs) -> Result<()> {
+    let old = take_png(&input)?;
+    let new = take_png(&args.other)?;
+    let changes = compare(&old, &new);
+    for change in &changes {
+        println!("{}", change);
+    }
+    if changes.is_empty() {
+        println!("The chunks are identical");
+    }
+    if args.pixels {
+        let (old_ihdr, new_ihdr) = (old.ihdr()?, new.ihdr()?);
+        if (old_ihdr.width, old_ihdr.height) == (new_ihdr.width, new_ihdr.height) {
+            println!("{}", compare_pixels(&old, &new)?);
+        } else {
+            println!(
+                "Pixels: {}x{} -> {}x{}, not compared",
+                old_ihdr.width, old_ihdr.height, new_ihdr.width, new_ihdr.height
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Matches a chunk type against a pattern where `?` stands for one letter
+/// and `*` for any number of them.
+fn matches_pattern(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => {
+            matches_pattern(rest, name)
+                || (!name.is_empty() && matches_pattern(pattern, &name[1..]))
+        }
+        (Some((&p, rest)), Some((&n, name))) => {
+            (p == b'?' || p == n) && matches_pattern(rest, name)
+        }
+        _ => false,
+    }
+}
+
+pub fn transplant<S: AsRef<Path>>(input: S, args: TransplantArgs) -> Result<()> {
+    if args.types.is_empty() && args.pattern.is_empty() && !args.private {
+        return Err(Error::Custom(
+            "Select the chunks to copy with --type, --pattern or --private",
+        ));
+    }
+    let mut types = Vec::with_capacity(args.types.len());
+    for name in &args.types {
+        let chunk_type = ChunkType::from_str(name)?;
+        if chunk_type.is_critical() {
+            return Err(Error::Custom("Only ancillary chunks can be transplanted"));
+        }
+        types.push(chunk_type);
+    }
+    let mut png = take_png(&input)?;
+    let source = take_png(&args.source)?;
+    let selected: Vec<&Chunk> = source
+        .chunks()
+        .iter()
+        .filter(|chunk| {
+            let chunk_type = chunk.chunk_type();
+            !chunk_type.is_critical()
+                && (types.contains(chunk_type)
+                    || (args.private && !chunk_type.is_public())
+                    || args
+                        .pattern
+                        .iter()
+                        .any(|pattern| matches_pattern(pattern.as_bytes(), chunk_type.bytes())))
+        })
+        .collect();
+    if selected.is_empty() {
+        return Err(Error::Custom("The source has no matching chunks"));
+    }
+
+    let mut copied = 0;
+    for chunk in selected {
+        let chunk_type = chunk.chunk_type();
+        if png
+            .chunks()
+            .iter()
+            .any(|c| c.chunk_type() == chunk_type && c.data() == chunk.data())
+        {
+            println!("Skipped {}, the image already has it", chunk_type);
+            continue;
+        }
+        if !chunk_type.is_safe_to_copy() {
+            eprintln!(
+                "Warning: {} is not safe to copy and may not match the image",
+                chunk_type
+            );
+        }
+        let copy = Chunk::new(chunk_type.clone(), chunk.data().to_vec());
+        match png.insert_chunk(copy) {
+            Some(_) => println!("Replaced {} ({} bytes)", chunk_type, chunk.data().len()),
+            None => println!("Copied {} ({} bytes)", chunk_type, chunk.data().len()),
+        }
+        copied += 1;
+    }
+    println!("{} chunks copied", copied);
+    if copied > 0 {
+        write_png(input, &png)?;
+    }
+    Ok(())
+}
+
+pub fn move_chunk<S: AsRef<Path>>(input: S, args: MoveArgs) -> Result<()> {
+    let mut png = take_png(&input)?;
+    let (from, chunk) = select_chunk(&png, &args.chunk)?;
+    let chunk_type = chunk.chunk_type().clone();
+    let to = args
+        .position
+        .checked_sub(1)
+        .ok_or(Error::Custom("Chunk index out of range"))?;
+    png.move_chunk(from, to)?;
+    if !args.force {
+        png.check_order()?;
+    }
+    println!("Moved {} from ({}) to ({})", chunk_type, from + 1, to + 1);
+    write_png(input, &png)
+}
+
+pub fn rename<S: AsRef<Path>>(input: S, args: RenameArgs) -> Result<()> {
+    let mut png = take_png(&input)?;
+    let (index, chunk) = select_chunk(&png, &args.chunk)?;
+    let old = chunk.chunk_type().clone();
+    let new = ChunkType::from_str(&args.chunk_type)?;
+    if !new.is_valid() {
+        return Err(Error::Custom(
+            "The reserved bit of the chunk type must be unset",
+        ));
+    }
+    if (old.is_critical() || new.is_critical()) && !args.force {
+        return Err(Error::Custom(
+            "Renaming critical chunks breaks the image, use --force to do it anyway",
+        ));
+    }
+    png.chunk_mut(index)
+        .expect("chunk was selected above")
+        .set_chunk_type(new.clone());
+    println!("Renamed ({}) {} to {}", index + 1, old, new);
+    write_png(input, &png)
+}
+
+pub fn replace_data<S: AsRef<Path>>(input: S, args: ReplaceDataArgs) -> Result<()> {
+    let mut png = take_png(&input)?;
+    let (index, chunk) = select_chunk(&png, &args.chunk)?;
+    let (chunk_type, old_len) = (chunk.chunk_type().clone(), chunk.data().len());
+    if chunk_type.is_critical() && !args.force {
+        return Err(Error::Custom(
+            "Replacing the data of critical chunks breaks the image, use --force to do it anyway",
+        ));
+    }
+    let data = std::fs::read(&args.file)?;
+    let new_len = data.len();
+    png.chunk_mut(index)
+        .expect("chunk was selected above")
+        .set_data(data);
+    println!(
+        "Replaced the data of ({}) {}: {} -> {} bytes",
+        index + 1,
+        chunk_type,
+        old_len,
+        new_len
+    );
+    if is_image_chunk(&chunk_type) {
+        apply_copy_policy(&mut png, args.unsafe_chunks);
+    }
+    write_png(input, &png)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{ihdr::ColorType, image::Image};
     use std::{fs::remove_file, path::PathBuf};
 
     fn make_copy_of_file(input: &str) -> PathBuf {
@@ -78,11 +893,18 @@ mod tests {
     #[test]
     fn test_encode() {
         let input = make_copy_of_file("encode.png");
-        let chunk_type = "RuST".to_string();
+        let chunk_type = "ruSt".to_string();
         let message = "Message".to_string();
         let args = EncodeArgs {
-            chunk_type,
-            message,
+            chunk_type: Some(chunk_type),
+            message: Some(message),
+            force: false,
+            password: None,
+            trailer: None,
+            deinterlace: false,
+            unsafe_chunks: CopyPolicy::Drop,
+            matrix: 1,
+            ecc: None,
         };
         let res = encode(&input, args);
         assert!(res.is_ok());
@@ -92,16 +914,29 @@ mod tests {
     #[test]
     fn test_decode() {
         let input = make_copy_of_file("decode.png");
-        let chunk_type = "RuST".to_string();
+        let chunk_type = "ruSt".to_string();
         let message = "Message".to_string();
         let args = EncodeArgs {
-            chunk_type,
-            message,
+            chunk_type: Some(chunk_type),
+            message: Some(message),
+            force: false,
+            password: None,
+            trailer: None,
+            deinterlace: false,
+            unsafe_chunks: CopyPolicy::Drop,
+            matrix: 1,
+            ecc: None,
         };
         let res = encode(&input, args);
         assert!(res.is_ok());
-        let chunk_type = "RuST".to_string();
-        let args = DecodeArgs { chunk_type };
+        let chunk_type = "ruSt".to_string();
+        let args = DecodeArgs {
+            chunk_type: Some(chunk_type),
+            password: None,
+            trailer: false,
+            matrix: 1,
+            ecc: None,
+        };
         let res = decode(&input, args);
         assert!(res.is_ok());
         remove_file(input).unwrap();
@@ -110,25 +945,735 @@ mod tests {
     #[test]
     fn test_remove() {
         let input = make_copy_of_file("remove.png");
-        let chunk_type = "RuST".to_string();
+        let chunk_type = "ruSt".to_string();
         let message = "Message".to_string();
         let args = EncodeArgs {
-            chunk_type,
-            message,
+            chunk_type: Some(chunk_type),
+            message: Some(message),
+            force: false,
+            password: None,
+            trailer: None,
+            deinterlace: false,
+            unsafe_chunks: CopyPolicy::Drop,
+            matrix: 1,
+            ecc: None,
         };
         let res = encode(&input, args);
         assert!(res.is_ok());
-        let chunk_type = "RuST".to_string();
-        let args = RemoveArgs { chunk_type };
+        let chunk_type = "ruSt".to_string();
+        let args = RemoveArgs {
+            chunk_type: Some(chunk_type),
+            unsafe_chunks: CopyPolicy::Drop,
+            trailer: false,
+        };
         let res = remove(&input, args);
         assert!(res.is_ok());
         remove_file(input).unwrap();
     }
 
+    #[test]
+    fn test_disguised_chunk_type() {
+        let input = make_copy_of_file("disguise.png");
+        let args = EncodeArgs {
+            chunk_type: Some("key:hunter2".to_string()),
+            message: Some("Message".to_string()),
+            force: false,
+            password: None,
+            trailer: None,
+            deinterlace: false,
+            unsafe_chunks: CopyPolicy::Drop,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(encode(&input, args).is_ok());
+        let png = take_png(&input).unwrap();
+        let chunk_type = crate::disguise::chunk_type_from_key("hunter2").to_string();
+        assert_eq!(
+            png.chunk_by_type(&chunk_type)
+                .unwrap()
+                .data_as_string()
+                .unwrap(),
+            "Message"
+        );
+        let args = DecodeArgs {
+            chunk_type: Some("key:hunter2".to_string()),
+            password: None,
+            trailer: false,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(decode(&input, args).is_ok());
+        let args = DecodeArgs {
+            chunk_type: Some("key:hunter3".to_string()),
+            password: None,
+            trailer: false,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(decode(&input, args).is_err());
+
+        let mut png = take_png(&input).unwrap();
+        png.insert_before_end(Chunk::new(ChunkType::from_str("caNv").unwrap(), vec![0; 8]));
+        write_png(&input, &png).unwrap();
+        let args = EncodeArgs {
+            chunk_type: Some("vendor".to_string()),
+            message: Some("Vendor".to_string()),
+            force: false,
+            password: None,
+            trailer: None,
+            deinterlace: false,
+            unsafe_chunks: CopyPolicy::Drop,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(encode(&input, args).is_ok());
+        let args = |chunk_type: &str| DecodeArgs {
+            chunk_type: Some(chunk_type.to_string()),
+            password: None,
+            trailer: false,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(decode(&input, args("vendor")).is_err());
+        assert!(decode(&input, args("vpAg")).is_ok());
+        let args = |chunk_type: &str| RemoveArgs {
+            chunk_type: Some(chunk_type.to_string()),
+            unsafe_chunks: CopyPolicy::Drop,
+            trailer: false,
+        };
+        assert!(remove(&input, args("vendor")).is_err());
+        assert!(remove(&input, args("vpAg")).is_ok());
+        let png = take_png(&input).unwrap();
+        assert!(png.chunk_by_type("vpAg").is_none());
+        assert_eq!(png.chunk_by_type("caNv").unwrap().data(), &[0; 8]);
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_text_cover() {
+        let input = make_copy_of_file("text_cover.png");
+        for keyword in &["text:Comment", "text:XML:com.adobe.xmp"] {
+            let args = EncodeArgs {
+                chunk_type: Some(keyword.to_string()),
+                message: Some("Message".to_string()),
+                force: false,
+                password: Some("hunter2".to_string()),
+                trailer: None,
+                deinterlace: false,
+                unsafe_chunks: CopyPolicy::Drop,
+                matrix: 1,
+                ecc: None,
+            };
+            assert!(encode(&input, args).is_ok());
+            let args = DecodeArgs {
+                chunk_type: Some(keyword.to_string()),
+                password: Some("hunter2".to_string()),
+                trailer: false,
+                matrix: 1,
+                ecc: None,
+            };
+            assert!(decode(&input, args).is_ok());
+            let args = DecodeArgs {
+                chunk_type: Some(keyword.to_string()),
+                password: Some("hunter3".to_string()),
+                trailer: false,
+                matrix: 1,
+                ecc: None,
+            };
+            assert!(decode(&input, args).is_err());
+        }
+        let png = take_png(&input).unwrap();
+        assert!(png.chunk_by_type("zTXt").is_some());
+        assert!(png.chunk_by_type("iTXt").is_some());
+
+        let args = EncodeArgs {
+            chunk_type: Some("text:Comment".to_string()),
+            message: Some("Message".to_string()),
+            force: false,
+            password: None,
+            trailer: None,
+            deinterlace: false,
+            unsafe_chunks: CopyPolicy::Drop,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(encode(&input, args).is_err());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_chunk() {
+        let input = make_copy_of_file("encrypted.png");
+        let args = EncodeArgs {
+            chunk_type: Some("ruSt".to_string()),
+            message: Some("Message".to_string()),
+            force: false,
+            password: Some("hunter2".to_string()),
+            trailer: None,
+            deinterlace: false,
+            unsafe_chunks: CopyPolicy::Drop,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(encode(&input, args).is_ok());
+        let png = take_png(&input).unwrap();
+        assert!(!png
+            .chunk_by_type("ruSt")
+            .unwrap()
+            .data()
+            .starts_with(b"Message"));
+        let args = DecodeArgs {
+            chunk_type: Some("ruSt".to_string()),
+            password: Some("hunter2".to_string()),
+            trailer: false,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(decode(&input, args).is_ok());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_trailer() {
+        let input = make_copy_of_file("trailer.png");
+        let chunks = take_png(&input).unwrap().chunks().len();
+        let args = EncodeArgs {
+            chunk_type: None,
+            message: None,
+            force: false,
+            password: None,
+            trailer: Some("Message".to_string()),
+            deinterlace: false,
+            unsafe_chunks: CopyPolicy::Drop,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(encode(&input, args).is_ok());
+        let png = take_png(&input).unwrap();
+        assert_eq!(png.chunks().len(), chunks);
+        assert_eq!(png.trailer(), b"Message");
+        assert!(print(&input, PrintArgs::default()).is_ok());
+        let args = PrintArgs {
+            stats: true,
+            #[cfg(feature = "serde")]
+            json: false,
+        };
+        assert!(print(&input, args).is_ok());
+
+        let args = DecodeArgs {
+            chunk_type: None,
+            password: None,
+            trailer: true,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(decode(&input, args).is_ok());
+        let args = RemoveArgs {
+            chunk_type: None,
+            unsafe_chunks: CopyPolicy::Drop,
+            trailer: true,
+        };
+        assert!(remove(&input, args).is_ok());
+        assert!(take_png(&input).unwrap().trailer().is_empty());
+        let args = DecodeArgs {
+            chunk_type: None,
+            password: None,
+            trailer: true,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(decode(&input, args).is_err());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_polyglot() {
+        let payload = PathBuf::new().join("assets").join("polyglot.txt");
+        std::fs::write(&payload, "Message").unwrap();
+        for chunk in &[None, Some("zpAk".to_string())] {
+            let input = make_copy_of_file("polyglot.png");
+            let args = PolyglotArgs {
+                files: vec![payload.clone()],
+                chunk: chunk.clone(),
+            };
+            assert!(polyglot(&input, args).is_ok());
+            let png = take_png(&input).unwrap();
+            assert_eq!(png.chunks().last().unwrap().chunk_type().bytes(), b"IEND");
+            let files = crate::zip::tests::read_archive(&std::fs::read(&input).unwrap());
+            assert_eq!(
+                files,
+                vec![("polyglot.txt".to_string(), b"Message".to_vec())]
+            );
+            remove_file(input).unwrap();
+        }
+        remove_file(payload).unwrap();
+    }
+
+    #[test]
+    fn test_detect() {
+        let input = make_copy_of_file("detect.png");
+        assert!(detect(&input, DetectArgs::default()).is_ok());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_diff() {
+        let input = make_copy_of_file("diff.png");
+        let other = make_copy_of_file("diff_other.png");
+        let mut png = take_png(&other).unwrap();
+        png.insert_before_end(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"Message".to_vec(),
+        ));
+        write_png(&other, &png).unwrap();
+        assert_eq!(compare(&take_png(&input).unwrap(), &png).len(), 1);
+        let args = DiffArgs {
+            other: other.clone(),
+            pixels: true,
+        };
+        assert!(diff(&input, args).is_ok());
+        remove_file(input).unwrap();
+        remove_file(other).unwrap();
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern(b"ruSt", b"ruSt"));
+        assert!(matches_pattern(b"r*", b"ruSt"));
+        assert!(matches_pattern(b"?u*t", b"ruSt"));
+        assert!(matches_pattern(b"*", b"IEND"));
+        assert!(!matches_pattern(b"r?", b"ruSt"));
+        assert!(!matches_pattern(b"*x", b"ruSt"));
+    }
+
+    #[test]
+    fn test_transplant() {
+        let input = make_copy_of_file("transplant.png");
+        let source = make_copy_of_file("transplant_source.png");
+        let mut png = take_png(&source).unwrap();
+        png.insert_before_end(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"Message".to_vec(),
+        ));
+        png.insert_before_end(Chunk::new(
+            ChunkType::from_str("prVx").unwrap(),
+            b"Other".to_vec(),
+        ));
+        write_png(&source, &png).unwrap();
+
+        let args = |types: &[&str], pattern: &[&str], private| TransplantArgs {
+            source: source.clone(),
+            types: types.iter().map(|t| t.to_string()).collect(),
+            pattern: pattern.iter().map(|p| p.to_string()).collect(),
+            private,
+        };
+        assert!(transplant(&input, args(&[], &[], false)).is_err());
+        assert!(transplant(&input, args(&["IDAT"], &[], false)).is_err());
+        assert!(transplant(&input, args(&["tIME"], &[], false)).is_err());
+        assert!(transplant(&input, args(&[], &["r*"], false)).is_ok());
+        let after = take_png(&input).unwrap();
+        assert_eq!(after.chunk_by_type("ruSt").unwrap().data(), b"Message");
+        assert!(after.chunk_by_type("prVx").is_none());
+
+        assert!(transplant(&input, args(&[], &[], true)).is_ok());
+        let after = take_png(&input).unwrap();
+        let chunks = after.chunks();
+        assert_eq!(chunks.len(), png.chunks().len());
+        assert_eq!(chunks[chunks.len() - 2].chunk_type().to_string(), "prVx");
+        assert_eq!(after.image().unwrap(), png.image().unwrap());
+        remove_file(input).unwrap();
+        remove_file(source).unwrap();
+    }
+
+    #[test]
+    fn test_move_chunk() {
+        let input = make_copy_of_file("move.png");
+        let before = take_png(&input).unwrap();
+        let last = before.chunks().len() - 1;
+        let args = |chunk: &str, position, force| MoveArgs {
+            chunk: chunk.to_string(),
+            position,
+            force,
+        };
+        assert!(move_chunk(&input, args("1", 3, false)).is_err());
+        assert!(move_chunk(&input, args("IEND", last, false)).is_err());
+        assert!(move_chunk(&input, args("1", 0, true)).is_err());
+        assert!(move_chunk(&input, args(&last.to_string(), 2, false)).is_ok());
+        let after = take_png(&input).unwrap();
+        assert_eq!(
+            after.chunks()[1].chunk_type(),
+            before.chunks()[last - 1].chunk_type()
+        );
+        assert!(move_chunk(&input, args("1", 3, true)).is_ok());
+        assert!(take_png(&input).unwrap().check_order().is_err());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_rename() {
+        let input = make_copy_of_file("rename.png");
+        let args = |chunk: &str, chunk_type: &str, force| RenameArgs {
+            chunk: chunk.to_string(),
+            chunk_type: chunk_type.to_string(),
+            force,
+        };
+        assert!(rename(&input, args("tEXt", "prVx", false)).is_ok());
+        assert!(rename(&input, args("prVx", "prvx", false)).is_err());
+        assert!(rename(&input, args("prVx", "PRVX", false)).is_err());
+        assert!(rename(&input, args("IHDR", "ihDR", false)).is_err());
+        let png = take_png(&input).unwrap();
+        assert!(png.chunk_by_type("prVx").is_some());
+        assert!(png.image().is_ok());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_replace_data() {
+        let input = make_copy_of_file("replace_data.png");
+        let file = PathBuf::from("assets/replace_data.bin");
+        std::fs::write(&file, b"Message").unwrap();
+        let args = ReplaceDataArgs {
+            chunk: "gAMA".to_string(),
+            file: file.clone(),
+            unsafe_chunks: CopyPolicy::Drop,
+            force: false,
+        };
+        assert!(replace_data(&input, args).is_ok());
+        let png = take_png(&input).unwrap();
+        assert_eq!(png.chunk_by_type("gAMA").unwrap().data(), b"Message");
+        let args = |force| ReplaceDataArgs {
+            chunk: "IHDR".to_string(),
+            file: file.clone(),
+            unsafe_chunks: CopyPolicy::Drop,
+            force,
+        };
+        assert!(replace_data(&input, args(false)).is_err());
+        assert!(replace_data(&input, args(true)).is_ok());
+        let png = take_png(&input).unwrap();
+        assert_eq!(png.chunk_by_type("IHDR").unwrap().data(), b"Message");
+        remove_file(input).unwrap();
+        remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_optimize() {
+        let input = make_copy_of_file("optimize.png");
+        let mut before = take_png(&input).unwrap();
+        let stored = zlib::compress_with_level(&before.image_data().unwrap(), 0);
+        before.replace_idat(&stored, 65536).unwrap();
+        before.insert_before_end(Chunk::new(ChunkType::from_str("vpAG").unwrap(), vec![0; 4]));
+        write_png(&input, &before).unwrap();
+        let args = OptimizeArgs {
+            dry_run: true,
+            ..OptimizeArgs::default()
+        };
+        assert!(optimize(&input, args).is_ok());
+        assert_eq!(take_png(&input).unwrap().as_bytes(), before.as_bytes());
+        let args = OptimizeArgs {
+            filter: vec![Strategy::Adaptive],
+            level: vec![9],
+            idat_size: 8192,
+            ..OptimizeArgs::default()
+        };
+        assert!(optimize(&input, args).is_ok());
+        let after = take_png(&input).unwrap();
+        assert!(after.idat().len() < before.idat().len());
+        let ihdr = before.ihdr().unwrap();
+        assert_eq!(
+            unfilter(&after.image_data().unwrap(), &ihdr).unwrap(),
+            unfilter(&before.image_data().unwrap(), &ihdr).unwrap()
+        );
+        assert!(after
+            .chunks()
+            .iter()
+            .all(|chunk| chunk.chunk_type().bytes() != b"IDAT" || chunk.data().len() <= 8192));
+        assert!(after.chunk_by_type("vpAG").is_none());
+
+        let args = OptimizeArgs {
+            level: vec![10],
+            ..OptimizeArgs::default()
+        };
+        assert!(optimize(&input, args).is_err());
+        remove_file(input).unwrap();
+    }
+
+    fn lsb_args(deinterlace: bool) -> EncodeArgs {
+        EncodeArgs {
+            chunk_type: Some("lsb".to_string()),
+            message: Some("Message".to_string()),
+            force: false,
+            password: None,
+            trailer: None,
+            deinterlace,
+            unsafe_chunks: CopyPolicy::Drop,
+            matrix: 1,
+            ecc: None,
+        }
+    }
+
+    #[test]
+    fn test_lsb_unsafe_chunks() {
+        let input = make_copy_of_file("lsb_unsafe.png");
+        let mut png = take_png(&input).unwrap();
+        png.insert_before_end(Chunk::new(ChunkType::from_str("RuST").unwrap(), vec![0; 4]));
+        png.insert_before_end(Chunk::new(ChunkType::from_str("vpAG").unwrap(), vec![0; 4]));
+        write_png(&input, &png).unwrap();
+        let args = EncodeArgs {
+            unsafe_chunks: CopyPolicy::Keep,
+            ..lsb_args(false)
+        };
+        assert!(encode(&input, args).is_ok());
+        assert!(take_png(&input).unwrap().chunk_by_type("vpAG").is_some());
+        assert!(encode(&input, lsb_args(false)).is_ok());
+        let png = take_png(&input).unwrap();
+        assert!(png.chunk_by_type("vpAG").is_none());
+        assert!(png.chunk_by_type("RuST").is_some());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_lsb_interlaced() {
+        let progressive = make_copy_of_file("lsb.png");
+        let interlaced = make_copy_of_file("lsb_interlaced.png");
+        let mut png = take_png(&interlaced).unwrap();
+        png.set_image(&png.stored_image().unwrap(), true).unwrap();
+        write_png(&interlaced, &png).unwrap();
+
+        assert!(encode(&progressive, lsb_args(false)).is_ok());
+        assert!(encode(&interlaced, lsb_args(false)).is_ok());
+        let progressive_png = take_png(&progressive).unwrap();
+        let interlaced_png = take_png(&interlaced).unwrap();
+        assert!(interlaced_png.ihdr().unwrap().is_interlaced());
+        assert_eq!(
+            progressive_png.stored_image().unwrap(),
+            interlaced_png.stored_image().unwrap()
+        );
+        for input in &[&progressive, &interlaced] {
+            let args = DecodeArgs {
+                chunk_type: Some("lsb".to_string()),
+                password: None,
+                trailer: false,
+                matrix: 1,
+                ecc: None,
+            };
+            assert!(decode(input, args).is_ok());
+        }
+
+        assert!(encode(&interlaced, lsb_args(true)).is_ok());
+        let png = take_png(&interlaced).unwrap();
+        assert!(!png.ihdr().unwrap().is_interlaced());
+        assert_eq!(
+            lsb::extract(&png.stored_image().unwrap(), Selection::Color, 1).unwrap(),
+            b"Message"
+        );
+        remove_file(progressive).unwrap();
+        remove_file(interlaced).unwrap();
+    }
+
+    #[test]
+    fn test_matrix() {
+        let input = make_copy_of_file("matrix.png");
+        let mut args = lsb_args(false);
+        args.matrix = 3;
+        assert!(encode(&input, args).is_ok());
+        let image = take_png(&input).unwrap().stored_image().unwrap();
+        assert_eq!(
+            lsb::extract(&image, Selection::Color, 3).unwrap(),
+            b"Message"
+        );
+
+        let before = take_png(&input).unwrap();
+        let capacity = lsb::capacity(&before.stored_image().unwrap(), Selection::Color, 3).unwrap();
+        let mut args = lsb_args(false);
+        args.message = Some("a".repeat(capacity + 1));
+        args.matrix = 3;
+        assert!(encode(&input, args).is_err());
+        assert_eq!(take_png(&input).unwrap().as_bytes(), before.as_bytes());
+
+        let mut args = lsb_args(false);
+        args.chunk_type = Some("ruSt".to_string());
+        args.matrix = 3;
+        assert!(encode(&input, args).is_err());
+        let args = DecodeArgs {
+            chunk_type: Some("lsb".to_string()),
+            password: None,
+            trailer: false,
+            matrix: 9,
+            ecc: None,
+        };
+        assert!(decode(&input, args).is_err());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_ecc() {
+        let input = make_copy_of_file("ecc.png");
+        let mut args = lsb_args(false);
+        args.ecc = Some(8);
+        assert!(encode(&input, args).is_ok());
+        // Damage one copy of the length and two bytes of the message.
+        let mut png = take_png(&input).unwrap();
+        let mut image = png.stored_image().unwrap();
+        for &sample in &[5, 100, 101, 200] {
+            image.data[sample] ^= 1;
+        }
+        png.set_image(&image, false).unwrap();
+        write_png(&input, &png).unwrap();
+
+        let damaged = lsb::extract(&image, Selection::Color, 1).unwrap();
+        assert_eq!(ecc::recover(&damaged, 8).unwrap(), (b"Message".to_vec(), 2));
+        let args = DecodeArgs {
+            chunk_type: Some("lsb".to_string()),
+            password: None,
+            trailer: false,
+            matrix: 1,
+            ecc: Some(8),
+        };
+        assert!(decode(&input, args).is_ok());
+        let args = DecodeArgs {
+            chunk_type: Some("lsb".to_string()),
+            password: None,
+            trailer: false,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(decode(&input, args).is_err());
+
+        let args = EncodeArgs {
+            chunk_type: Some("ruSt".to_string()),
+            ecc: Some(8),
+            ..lsb_args(false)
+        };
+        assert!(encode(&input, args).is_err());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_alpha() {
+        let input = make_copy_of_file("alpha.png");
+        // The left half becomes transparent.
+        let mut png = take_png(&input).unwrap();
+        let rgb = png.image().unwrap();
+        let data = rgb
+            .data
+            .chunks(3)
+            .enumerate()
+            .flat_map(|(i, c)| {
+                let alpha = if i as u32 % rgb.width < rgb.width / 2 {
+                    0
+                } else {
+                    255
+                };
+                vec![c[0], c[1], c[2], alpha]
+            })
+            .collect();
+        let rgba = Image::new(rgb.width, rgb.height, ColorType::Rgba, 8, data).unwrap();
+        png.set_image(&rgba, false).unwrap();
+        write_png(&input, &png).unwrap();
+
+        let mut args = lsb_args(false);
+        args.chunk_type = Some("alpha".to_string());
+        assert!(encode(&input, args).is_ok());
+        let image = take_png(&input).unwrap().stored_image().unwrap();
+        let selection = Selection::Transparent { alpha: false };
+        assert_eq!(lsb::extract(&image, selection, 1).unwrap(), b"Message");
+        // Visible pixels are untouched.
+        for (i, (a, b)) in image.data.chunks(4).zip(rgba.data.chunks(4)).enumerate() {
+            if i as u32 % rgb.width >= rgb.width / 2 {
+                assert_eq!(a, b);
+            }
+        }
+        let args = DecodeArgs {
+            chunk_type: Some("alpha".to_string()),
+            password: None,
+            trailer: false,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(decode(&input, args).is_ok());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_palette() {
+        let input = make_copy_of_file("palette.png");
+        // Quantize to two bits per channel, 64 colors.
+        let rgb = take_png(&input).unwrap().image().unwrap();
+        let indices = rgb
+            .data
+            .chunks(3)
+            .map(|c| (c[0] >> 6) << 4 | (c[1] >> 6) << 2 | c[2] >> 6)
+            .collect();
+        let indexed = Image::new(rgb.width, rgb.height, ColorType::Indexed, 8, indices).unwrap();
+        let colors = (0..64u8)
+            .flat_map(|i| vec![(i >> 4) * 85, (i >> 2 & 3) * 85, (i & 3) * 85])
+            .collect();
+        let chunk = |name: &str, data| Chunk::new(ChunkType::from_str(name).unwrap(), data);
+        let mut png = Png::from_chunks(vec![
+            indexed.ihdr(false).to_chunk(),
+            chunk("PLTE", colors),
+            chunk("IDAT", Vec::new()),
+            chunk("IEND", Vec::new()),
+        ]);
+        png.set_image(&indexed, false).unwrap();
+        write_png(&input, &png).unwrap();
+        let pixels = png.image().unwrap();
+
+        let mut args = lsb_args(false);
+        assert!(encode(&input, args).is_err());
+        args = lsb_args(false);
+        args.chunk_type = Some("palette".to_string());
+        assert!(encode(&input, args).is_ok());
+        let png = take_png(&input).unwrap();
+        assert_eq!(png.image().unwrap(), pixels);
+        assert_eq!(palette::extract(&png, 1).unwrap(), b"Message");
+        let args = DecodeArgs {
+            chunk_type: Some("palette".to_string()),
+            password: None,
+            trailer: false,
+            matrix: 1,
+            ecc: None,
+        };
+        assert!(decode(&input, args).is_ok());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_encode_rejects_critical_chunk() {
+        let input = make_copy_of_file("encode_critical.png");
+        let args = EncodeArgs {
+            chunk_type: Some("RuST".to_string()),
+            message: Some("Message".to_string()),
+            force: false,
+            password: None,
+            trailer: None,
+            deinterlace: false,
+            unsafe_chunks: CopyPolicy::Drop,
+            matrix: 1,
+            ecc: None,
+        };
+        let res = encode(&input, args);
+        assert!(res.is_err());
+        let args = EncodeArgs {
+            chunk_type: Some("RuST".to_string()),
+            message: Some("Message".to_string()),
+            force: true,
+            password: None,
+            trailer: None,
+            deinterlace: false,
+            unsafe_chunks: CopyPolicy::Drop,
+            matrix: 1,
+            ecc: None,
+        };
+        let res = encode(&input, args);
+        assert!(res.is_ok());
+        remove_file(input).unwrap();
+    }
+
     #[test]
     fn test_print() {
         let input = make_copy_of_file("print.png");
-        let res = print(&input);
+        let res = print(&input, PrintArgs::default());
         assert!(res.is_ok());
         remove_file(input).unwrap();
     }
@@ -136,24 +1681,279 @@ mod tests {
     #[test]
     fn test_all_one() {
         let input = make_copy_of_file("all_in.png");
-        let chunk_type = "RuST".to_string();
+        let chunk_type = "ruSt".to_string();
         let message = "Message".to_string();
         let args = EncodeArgs {
-            chunk_type,
-            message,
+            chunk_type: Some(chunk_type),
+            message: Some(message),
+            force: false,
+            password: None,
+            trailer: None,
+            deinterlace: false,
+            unsafe_chunks: CopyPolicy::Drop,
+            matrix: 1,
+            ecc: None,
         };
         let res = encode(&input, args);
         assert!(res.is_ok());
-        let chunk_type = "RuST".to_string();
-        let args = DecodeArgs { chunk_type };
+        let chunk_type = "ruSt".to_string();
+        let args = DecodeArgs {
+            chunk_type: Some(chunk_type),
+            password: None,
+            trailer: false,
+            matrix: 1,
+            ecc: None,
+        };
         let res = decode(&input, args);
         assert!(res.is_ok());
-        let chunk_type = "RuST".to_string();
-        let args = RemoveArgs { chunk_type };
+        let chunk_type = "ruSt".to_string();
+        let args = RemoveArgs {
+            chunk_type: Some(chunk_type),
+            unsafe_chunks: CopyPolicy::Drop,
+            trailer: false,
+        };
+        let res = remove(&input, args);
+        assert!(res.is_ok());
+        let res = print(&input, PrintArgs::default());
+        assert!(res.is_ok());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_inspect() {
+        let input = make_copy_of_file("inspect.png");
+        let args = InspectArgs {
+            chunk: "IHDR".to_string(),
+            offset: 4,
+            length: Some(8),
+            decode: true,
+        };
+        let res = inspect(&input, args);
+        assert!(res.is_ok());
+        let args = InspectArgs {
+            chunk: "IHDR".to_string(),
+            offset: 4,
+            length: Some(usize::MAX),
+            decode: false,
+        };
+        let res = inspect(&input, args);
+        assert!(res.is_ok());
+        let args = InspectArgs {
+            chunk: "1".to_string(),
+            offset: 14,
+            length: None,
+            decode: false,
+        };
+        let res = inspect(&input, args);
+        assert!(res.is_err());
+        remove_file(input).unwrap();
+    }
+
+    fn text_entry(keyword: &str, text: &str) -> TextEntryArgs {
+        TextEntryArgs {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            compress: false,
+            international: false,
+            language: None,
+            translated_keyword: None,
+        }
+    }
+
+    #[test]
+    fn test_text() {
+        let input = make_copy_of_file("text.png");
+        let mut png = take_png(&input).unwrap();
+        png.insert_before_end(Chunk::new(ChunkType::from_str("zTXt").unwrap(), vec![0; 4]));
+        write_png(&input, &png).unwrap();
+        let command = TextCommand::Add(text_entry("Title", "Dice"));
+        let res = text(&input, TextArgs { command });
+        assert!(res.is_ok());
+
+        let mut entry = text_entry("Title", "Würfel");
+        entry.compress = true;
+        let res = text(
+            &input,
+            TextArgs {
+                command: TextCommand::Edit(entry),
+            },
+        );
+        assert!(res.is_ok());
+        let texts = readable_text_chunks(&take_png(&input).unwrap());
+        let title = texts.iter().find(|text| text.keyword == "Title").unwrap();
+        assert_eq!(title.kind, TextKind::Compressed);
+        assert_eq!(title.text, "Würfel");
+
+        let res = text(
+            &input,
+            TextArgs {
+                command: TextCommand::List,
+            },
+        );
+        assert!(res.is_ok());
+        let command = TextCommand::Remove {
+            keyword: "Title".to_string(),
+        };
+        let res = text(&input, TextArgs { command });
+        assert!(res.is_ok());
+        let texts = readable_text_chunks(&take_png(&input).unwrap());
+        assert!(texts.iter().all(|text| text.keyword != "Title"));
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_meta() {
+        let input = make_copy_of_file("meta.png");
+        let args = MetaArgs {
+            dpi: Some(300.0),
+            srgb: Some(Srgb::Perceptual),
+            remove: vec!["cHRM".to_string()],
+            ..MetaArgs::default()
+        };
+        let res = meta(&input, args);
+        assert!(res.is_ok());
+        let png = take_png(&input).unwrap();
+        assert!(png.meta::<PhysicalDimensions>().unwrap().is_some());
+        assert_eq!(png.meta::<Srgb>().unwrap(), Some(Srgb::Perceptual));
+        assert!(png.meta::<Chromaticities>().unwrap().is_none());
+
+        let res = meta(&input, MetaArgs::default());
+        assert!(res.is_ok());
+
+        let idat = png.idat();
+        for chunk_type in &["IDAT", "tEXt"] {
+            let args = MetaArgs {
+                remove: vec![chunk_type.to_string()],
+                ..MetaArgs::default()
+            };
+            assert!(meta(&input, args).is_err());
+        }
+        assert_eq!(take_png(&input).unwrap().idat(), idat);
+        for &value in &[0.0, -1.0, f64::NAN] {
+            let args = MetaArgs {
+                gamma: Some(value),
+                ..MetaArgs::default()
+            };
+            assert!(meta(&input, args).is_err());
+        }
+        let args = MetaArgs {
+            dpi: Some(-300.0),
+            ..MetaArgs::default()
+        };
+        assert!(meta(&input, args).is_err());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_exif() {
+        let input = make_copy_of_file("exif.png");
+        let mut png = take_png(&input).unwrap();
+        let chunk_type = ChunkType::from_str("eXIf").unwrap();
+        png.insert_before_end(Chunk::new(chunk_type, crate::exif::tests::testing_exif()));
+        write_png(&input, &png).unwrap();
+
+        let args = ExifArgs {
+            redact_gps: true,
+            redact: vec!["Make".to_string()],
+            ..ExifArgs::default()
+        };
+        let res = exif(&input, args);
+        assert!(res.is_ok());
+        let exif_data: Exif = take_png(&input).unwrap().meta().unwrap().unwrap();
+        assert_eq!(exif_data.entries().len(), 2);
+
+        let res = print(&input, PrintArgs::default());
+        assert!(res.is_ok());
+        let args = ExifArgs {
+            strip: true,
+            ..ExifArgs::default()
+        };
+        let res = exif(&input, args);
+        assert!(res.is_ok());
+        assert!(take_png(&input).unwrap().chunk_by_type("eXIf").is_none());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_strip() {
+        let input = make_copy_of_file("strip.png");
+        let mut png = take_png(&input).unwrap();
+        png.set_trailer(b"Message".to_vec());
+        write_png(&input, &png).unwrap();
+        let args = StripArgs {
+            keep: vec!["gAMA".to_string()],
+            dry_run: true,
+        };
+        let res = strip(&input, args);
+        assert!(res.is_ok());
+        assert!(take_png(&input).unwrap().chunk_by_type("tEXt").is_some());
+
+        let args = StripArgs {
+            keep: vec!["gAMA".to_string()],
+            dry_run: false,
+        };
+        let res = strip(&input, args);
+        assert!(res.is_ok());
+        let png = take_png(&input).unwrap();
+        assert!(png.chunk_by_type("gAMA").is_some());
+        assert!(
+            png.chunks()
+                .iter()
+                .all(|chunk| chunk.chunk_type().is_critical()
+                    || chunk.chunk_type().bytes() == b"gAMA")
+        );
+        assert!(png.trailer().is_empty());
+        remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_remove_critical_drops_unsafe_chunks() {
+        let input = make_copy_of_file("remove_critical.png");
+        let mut png = take_png(&input).unwrap();
+        png.insert_before_end(Chunk::new(ChunkType::from_str("vpAG").unwrap(), vec![0; 4]));
+        png.insert_before_end(Chunk::new(ChunkType::from_str("RuST").unwrap(), vec![0; 4]));
+        write_png(&input, &png).unwrap();
+
+        let args = RemoveArgs {
+            chunk_type: Some("RuST".to_string()),
+            unsafe_chunks: CopyPolicy::Warn,
+            trailer: false,
+        };
         let res = remove(&input, args);
         assert!(res.is_ok());
-        let res = print(&input);
+        assert!(take_png(&input).unwrap().chunk_by_type("vpAG").is_some());
+
+        // A payload chunk is critical but leaves the image alone.
+        png.insert_before_end(Chunk::new(ChunkType::from_str("RuST").unwrap(), vec![0; 4]));
+        write_png(&input, &png).unwrap();
+        let args = |chunk_type: &str| RemoveArgs {
+            chunk_type: Some(chunk_type.to_string()),
+            unsafe_chunks: CopyPolicy::Drop,
+            trailer: false,
+        };
+        assert!(remove(&input, args("RuST")).is_ok());
+        assert!(take_png(&input).unwrap().chunk_by_type("vpAG").is_some());
+
+        assert!(remove(&input, args("IDAT")).is_ok());
+        let png = take_png(&input).unwrap();
+        assert!(png.chunk_by_type("vpAG").is_none());
+        assert!(png.chunk_by_type("gAMA").is_some());
+        remove_file(input).unwrap();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_build() {
+        let input = make_copy_of_file("build.png");
+        let json = PathBuf::new().join("assets").join("build.json");
+        let png = take_png(&input).unwrap();
+        std::fs::write(&json, serde_json::to_string(&png).unwrap()).unwrap();
+        remove_file(&input).unwrap();
+
+        let res = build(&input, BuildArgs { json: json.clone() });
         assert!(res.is_ok());
+        assert_eq!(take_png(&input).unwrap().as_bytes(), png.as_bytes());
+        remove_file(json).unwrap();
         remove_file(input).unwrap();
     }
 }
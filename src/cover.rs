@@ -0,0 +1,66 @@
+use crate::{
+    cipher::{from_text, to_text},
+    text::{TextChunk, TextKind},
+};
+
+/// Keyword Adobe software stores XMP packets under.
+pub const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+
+const XMP_HEADER: &str = "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+<rdf:Description rdf:about=\"\" xmlns:xmpMM=\"http://ns.adobe.com/xap/1.0/mm/\" \
+xmpMM:DocumentID=\"xmp.did:";
+const XMP_FOOTER: &str = "\"/>\n </rdf:RDF>\n</x:xmpmeta>\n<?xpacket end=\"w\"?>";
+
+/// Wraps a binary payload in an ordinary looking text chunk. XMP packets go
+/// into an uncompressed iTXt chunk like Adobe writes them, with the payload
+/// posing as the document id; anything else becomes a zTXt chunk.
+pub fn cover_text(keyword: &str, payload: &[u8]) -> TextChunk {
+    if keyword == XMP_KEYWORD {
+        let packet = format!("{}{}{}", XMP_HEADER, to_text(payload), XMP_FOOTER);
+        TextChunk::new(
+            TextKind::International { compressed: false },
+            keyword,
+            &packet,
+        )
+    } else {
+        TextChunk::new(TextKind::Compressed, keyword, &to_text(payload))
+    }
+}
+
+/// Extracts the payload stored by [`cover_text`], if the text looks like one.
+pub fn uncover_text(text: &TextChunk) -> Option<Vec<u8>> {
+    let encoded = if text.keyword == XMP_KEYWORD {
+        let start = text.text.find(XMP_HEADER)? + XMP_HEADER.len();
+        let len = text.text[start..].find('"')?;
+        &text.text[start..start + len]
+    } else {
+        &text.text
+    };
+    from_text(encoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cover_text() {
+        let text = cover_text("Comment", &[1, 2, 3]);
+        assert_eq!(text.kind, TextKind::Compressed);
+        assert_eq!(text.text, "AQID");
+        assert_eq!(uncover_text(&text).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_cover_xmp() {
+        let text = cover_text(XMP_KEYWORD, &[1, 2, 3]);
+        assert_eq!(text.kind, TextKind::International { compressed: false });
+        assert!(text.text.contains("xmpMM:DocumentID=\"xmp.did:AQID\""));
+        assert_eq!(uncover_text(&text).unwrap(), vec![1, 2, 3]);
+
+        let plain = TextChunk::new(TextKind::Plain, XMP_KEYWORD, "<x:xmpmeta/>");
+        assert!(uncover_text(&plain).is_none());
+    }
+}
@@ -0,0 +1,427 @@
+use crate::{
+    disguise::VENDOR_CHUNK_TYPES,
+    ihdr::ColorType,
+    image::{read_bits, Image},
+    stats::{entropy, histogram, max_entropy},
+    Png,
+};
+
+/// Chunks whose data is compressed anyway, so high entropy is expected.
+const COMPRESSED_CHUNKS: [&[u8; 4]; 5] = [b"IDAT", b"zTXt", b"iTXt", b"iCCP", b"fdAT"];
+
+/// Shorter data cannot tell random bytes from text.
+const MIN_ENTROPY_LEN: usize = 64;
+
+/// Share of the maximum entropy above which data looks encrypted.
+const HIGH_ENTROPY: f64 = 0.9;
+
+/// Fewer samples give the statistical tests too little to go on.
+const MIN_SAMPLES: usize = 1024;
+
+/// Result of one detection method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub method: &'static str,
+    /// From 0, nothing suspicious, to 1, almost certainly hidden data.
+    pub score: f64,
+    pub notes: Vec<String>,
+}
+
+/// Runs every detection method on `png`.
+pub fn analyze(png: &Png) -> Vec<Finding> {
+    let mut findings = vec![chunk_types(png), trailer(png), high_entropy(png)];
+    match png.stored_image() {
+        Ok(image) => {
+            let samples = samples(&image);
+            findings.push(chi_square(&samples));
+            if image.color_type != ColorType::Indexed {
+                findings.push(rs_analysis(&samples));
+            }
+        }
+        Err(e) => findings.push(Finding {
+            method: "Pixels",
+            score: 0.0,
+            notes: vec![format!("Unable to decode the pixels: {}", e)],
+        }),
+    }
+    findings
+}
+
+fn chunk_types(png: &Png) -> Finding {
+    let mut score: f64 = 0.0;
+    let mut notes = Vec::new();
+    for chunk in png.chunks() {
+        let chunk_type = chunk.chunk_type();
+        if chunk_type.is_standard() {
+            continue;
+        }
+        let name = chunk_type.to_string();
+        if VENDOR_CHUNK_TYPES.contains(&name.as_str()) {
+            score = score.max(0.5);
+            notes.push(format!(
+                "{} ({} bytes, written by image editors)",
+                name,
+                chunk.data().len()
+            ));
+        } else {
+            score = 1.0;
+            notes.push(format!("{} ({} bytes)", name, chunk.data().len()));
+        }
+    }
+    Finding {
+        method: "Non-standard chunks",
+        score,
+        notes,
+    }
+}
+
+fn trailer(png: &Png) -> Finding {
+    let trailer = png.trailer();
+    let notes = if trailer.is_empty() {
+        Vec::new()
+    } else {
+        vec![format!(
+            "{} bytes, {:.2} bits of entropy per byte",
+            trailer.len(),
+            entropy(trailer)
+        )]
+    };
+    Finding {
+        method: "Data after IEND",
+        score: if trailer.is_empty() { 0.0 } else { 1.0 },
+        notes,
+    }
+}
+
+/// Entropy of chunk data relative to the highest entropy data of that length
+/// can have. Text and metadata stay well below, encrypted data reaches it.
+fn high_entropy(png: &Png) -> Finding {
+    let mut score: f64 = 0.0;
+    let mut notes = Vec::new();
+    for chunk in png.chunks() {
+        let data = chunk.data();
+        if data.len() < MIN_ENTROPY_LEN || COMPRESSED_CHUNKS.contains(&chunk.chunk_type().bytes()) {
+            continue;
+        }
+        let bits = entropy(data);
+        let ratio = bits / max_entropy(data.len());
+        score = score.max(ratio);
+        if ratio >= HIGH_ENTROPY {
+            notes.push(format!(
+                "{} ({:.2} bits per byte)",
+                chunk.chunk_type(),
+                bits
+            ));
+        }
+    }
+    Finding {
+        method: "High-entropy chunks",
+        score,
+        notes,
+    }
+}
+
+/// Color samples of every channel row by row, leaving out alpha. Palette
+/// images give their indices, 16 bit samples their low byte.
+fn samples(image: &Image) -> Vec<Vec<u8>> {
+    let channels = match image.color_type {
+        ColorType::GrayscaleAlpha | ColorType::Rgba => image.channels() - 1,
+        _ => image.channels(),
+    };
+    let bits = image.bit_depth as usize;
+    let mut rows = Vec::new();
+    for y in 0..image.height {
+        let row = image.row(y);
+        for channel in 0..channels {
+            rows.push(
+                (0..image.width as usize)
+                    .map(|x| {
+                        let sample = x * image.channels() + channel;
+                        match bits {
+                            16 => row[sample * 2 + 1],
+                            8 => row[sample],
+                            _ => read_bits(row, sample * bits, bits),
+                        }
+                    })
+                    .collect(),
+            );
+        }
+    }
+    rows
+}
+
+/// Natural logarithm of the gamma function, Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |acc, (i, c)| {
+            acc + c / (x + i as f64 + 1.0)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// Regularized lower incomplete gamma function P(a, x).
+fn gamma_p(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let prefix = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..1000 {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term < sum * 1e-15 {
+                break;
+            }
+        }
+        sum * prefix
+    } else {
+        // Continued fraction of the upper function, Lentz's method.
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        1.0 - prefix * h
+    }
+}
+
+/// Probability that the pairs of values differing only in the least
+/// significant bit occur equally often, as they do after embedding.
+fn pair_probability(samples: &[u8]) -> Option<f64> {
+    let histogram = histogram(samples);
+    let mut chi = 0.0;
+    let mut categories = 0;
+    for pair in histogram.chunks(2) {
+        let expected = (pair[0] + pair[1]) as f64 / 2.0;
+        if expected < 5.0 {
+            continue;
+        }
+        chi += (pair[0] as f64 - expected).powi(2) / expected;
+        categories += 1;
+    }
+    if categories < 2 {
+        return None;
+    }
+    Some(1.0 - gamma_p((categories - 1) as f64 / 2.0, chi / 2.0))
+}
+
+/// Chi-square attack of Westfeld and Pfitzmann. Messages are usually hidden
+/// from the start of the image, so growing parts of it are tested and the
+/// most suspicious one counts.
+fn chi_square(rows: &[Vec<u8>]) -> Finding {
+    let samples: Vec<u8> = rows.concat();
+    let mut score: f64 = 0.0;
+    let mut notes = Vec::new();
+    for &part in &[64, 16, 4, 1] {
+        let len = samples.len() / part;
+        if len < MIN_SAMPLES {
+            continue;
+        }
+        if let Some(p) = pair_probability(&samples[..len]) {
+            if p > score {
+                score = p;
+                notes = vec![format!(
+                    "Embedding probability {:.3} in the first {} samples",
+                    p, len
+                )];
+            }
+        }
+    }
+    Finding {
+        method: "Chi-square",
+        score,
+        notes,
+    }
+}
+
+fn flip(value: i32, mask: i32) -> i32 {
+    match mask {
+        1 => value ^ 1,
+        -1 => ((value + 1) ^ 1) - 1,
+        _ => value,
+    }
+}
+
+fn smoothness(group: &[i32]) -> i32 {
+    group.windows(2).map(|pair| (pair[1] - pair[0]).abs()).sum()
+}
+
+/// Shares of regular and singular groups for the mask and the negated mask.
+fn rs_counts(groups: &[[i32; 4]]) -> [f64; 4] {
+    const MASK: [i32; 4] = [0, 1, 1, 0];
+    let mut counts = [0usize; 4];
+    for group in groups {
+        let before = smoothness(group);
+        for (sign, offset) in [(1, 0), (-1, 2)] {
+            let flipped: Vec<i32> = group
+                .iter()
+                .zip(&MASK)
+                .map(|(&value, &mask)| flip(value, sign * mask))
+                .collect();
+            let after = smoothness(&flipped);
+            if after > before {
+                counts[offset] += 1;
+            } else if after < before {
+                counts[offset + 1] += 1;
+            }
+        }
+    }
+    counts.map(|count| count as f64 / groups.len() as f64)
+}
+
+/// RS analysis of Fridrich, Goljan and Du: estimates the share of samples
+/// whose least significant bit carries a message from how flipping bits
+/// changes the smoothness of small groups of neighbouring samples.
+fn rs_analysis(rows: &[Vec<u8>]) -> Finding {
+    let groups: Vec<[i32; 4]> = rows
+        .iter()
+        .flat_map(|row| row.chunks_exact(4))
+        .map(|group| [0, 1, 2, 3].map(|i| group[i] as i32))
+        .collect();
+    let mut finding = Finding {
+        method: "RS analysis",
+        score: 0.0,
+        notes: Vec::new(),
+    };
+    if groups.len() * 4 < MIN_SAMPLES {
+        return finding;
+    }
+    let inverted: Vec<[i32; 4]> = groups.iter().map(|group| group.map(|v| v ^ 1)).collect();
+    let [r, s, r_neg, s_neg] = rs_counts(&groups);
+    let [r_inv, s_inv, r_neg_inv, s_neg_inv] = rs_counts(&inverted);
+    let (d0, d1) = (r - s, r_inv - s_inv);
+    let (d0_neg, d1_neg) = (r_neg - s_neg, r_neg_inv - s_neg_inv);
+    let a = 2.0 * (d1 + d0);
+    let b = d0_neg - d1_neg - d1 - 3.0 * d0;
+    let c = d0 - d0_neg;
+    let z = if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return finding;
+        }
+        -c / b
+    } else {
+        // Sampling noise can push the discriminant below zero when R and S
+        // almost meet, which happens when nearly every pixel carries data.
+        // The real part of the roots is the closest estimate then.
+        let root = (b * b - 4.0 * a * c).max(0.0).sqrt();
+        let (z1, z2) = ((-b + root) / (2.0 * a), (-b - root) / (2.0 * a));
+        if z1.abs() < z2.abs() {
+            z1
+        } else {
+            z2
+        }
+    };
+    let estimate = (z / (z - 0.5)).clamp(0.0, 1.0);
+    finding.score = estimate;
+    finding.notes.push(format!(
+        "About {:.0}% of the samples carry a message",
+        estimate * 100.0
+    ));
+    finding
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsb::{self, Selection};
+    use std::convert::TryFrom;
+
+    fn pic() -> Png {
+        Png::try_from(&std::fs::read("assets/pic.png").unwrap()[..]).unwrap()
+    }
+
+    fn score(findings: &[Finding], method: &str) -> f64 {
+        findings
+            .iter()
+            .find(|finding| finding.method == method)
+            .unwrap()
+            .score
+    }
+
+    #[test]
+    fn test_gamma_p() {
+        // 5% critical value of the chi-square distribution with 1 and 10
+        // degrees of freedom.
+        assert!((gamma_p(0.5, 3.841 / 2.0) - 0.95).abs() < 1e-3);
+        assert!((gamma_p(5.0, 18.307 / 2.0) - 0.95).abs() < 1e-3);
+        assert!((ln_gamma(5.0) - 24f64.ln()).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_clean_image() {
+        let findings = analyze(&pic());
+        assert_eq!(score(&findings, "Non-standard chunks"), 0.0);
+        assert_eq!(score(&findings, "Data after IEND"), 0.0);
+        assert!(score(&findings, "High-entropy chunks") < HIGH_ENTROPY);
+        assert!(score(&findings, "Chi-square") < 0.5);
+        assert!(score(&findings, "RS analysis") < 0.1);
+    }
+
+    #[test]
+    fn test_hidden_data() {
+        let mut png = pic();
+        let mut image = png.stored_image().unwrap();
+        let capacity = lsb::capacity(&image, Selection::Color, 1).unwrap();
+        let payload: Vec<u8> = (0..capacity as u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        lsb::embed(&mut image, &payload, Selection::Color, 1).unwrap();
+        png.set_image(&image, false).unwrap();
+        png.insert_before_end(crate::Chunk::new(
+            "ruSt".parse().unwrap(),
+            payload[..300].to_vec(),
+        ));
+        png.set_trailer(b"secret".to_vec());
+
+        let findings = analyze(&png);
+        assert_eq!(score(&findings, "Non-standard chunks"), 1.0);
+        assert_eq!(score(&findings, "Data after IEND"), 1.0);
+        assert!(score(&findings, "High-entropy chunks") > HIGH_ENTROPY);
+        assert!(score(&findings, "Chi-square") > 0.9);
+        assert!(score(&findings, "RS analysis") > 0.8);
+    }
+
+    #[test]
+    fn test_private_critical_chunk() {
+        let mut png = pic();
+        png.insert_before_end(crate::Chunk::new("RuST".parse().unwrap(), vec![0; 4]));
+
+        let findings = analyze(&png);
+        assert_eq!(score(&findings, "Non-standard chunks"), 1.0);
+    }
+}
@@ -0,0 +1,424 @@
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    ihdr::ColorType,
+    image::{read_bits, Image},
+    Error, Png, Result,
+};
+use std::fmt;
+
+/// Difference between two files. Chunk indices are 0-based and shown 1-based
+/// as by `print`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Only the new file has the chunk.
+    Added {
+        index: usize,
+        chunk_type: ChunkType,
+        len: usize,
+    },
+    /// Only the old file has the chunk.
+    Removed {
+        index: usize,
+        chunk_type: ChunkType,
+        len: usize,
+    },
+    /// The chunk changed its place relative to the other chunks.
+    Moved {
+        from: usize,
+        to: usize,
+        chunk_type: ChunkType,
+    },
+    /// The chunk kept its type but not its data, so the CRC differs too.
+    Changed {
+        index: usize,
+        chunk_type: ChunkType,
+        old_len: usize,
+        new_len: usize,
+        old_crc: u32,
+        new_crc: u32,
+    },
+    /// The data after IEND differs.
+    Trailer { old_len: usize, new_len: usize },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added {
+                index,
+                chunk_type,
+                len,
+            } => write!(f, "Added ({}) {}, {} bytes", index + 1, chunk_type, len),
+            Change::Removed {
+                index,
+                chunk_type,
+                len,
+            } => write!(f, "Removed ({}) {}, {} bytes", index + 1, chunk_type, len),
+            Change::Moved {
+                from,
+                to,
+                chunk_type,
+            } => write!(
+                f,
+                "Moved {} from ({}) to ({})",
+                chunk_type,
+                from + 1,
+                to + 1
+            ),
+            Change::Changed {
+                index,
+                chunk_type,
+                old_len,
+                new_len,
+                old_crc,
+                new_crc,
+            } => write!(
+                f,
+                "Changed ({}) {}: {} -> {} bytes, crc {} -> {}",
+                index + 1,
+                chunk_type,
+                old_len,
+                new_len,
+                old_crc,
+                new_crc
+            ),
+            Change::Trailer { old_len, new_len } => {
+                write!(f, "Trailer: {} -> {} bytes after IEND", old_len, new_len)
+            }
+        }
+    }
+}
+
+fn same_chunk(a: &Chunk, b: &Chunk) -> bool {
+    a.chunk_type() == b.chunk_type() && a.data() == b.data()
+}
+
+/// Indices into `values` of a longest strictly increasing subsequence.
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    // Length of the longest subsequence ending at every value and its
+    // predecessor there.
+    let mut lengths = vec![1; values.len()];
+    let mut previous = vec![None; values.len()];
+    for i in 0..values.len() {
+        for j in 0..i {
+            if values[j] < values[i] && lengths[j] + 1 > lengths[i] {
+                lengths[i] = lengths[j] + 1;
+                previous[i] = Some(j);
+            }
+        }
+    }
+    let mut end = (0..values.len()).max_by_key(|&i| (lengths[i], std::cmp::Reverse(i)));
+    let mut sequence = Vec::new();
+    while let Some(i) = end {
+        sequence.push(i);
+        end = previous[i];
+    }
+    sequence.reverse();
+    sequence
+}
+
+/// Compares `old` with `new` chunk by chunk. Identical chunks are paired up
+/// first, the remaining ones by type in file order, so an edited text chunk
+/// shows up as changed rather than removed and added. Paired chunks that
+/// are out of order are reported as moved, keeping the number of moves low.
+pub fn compare(old: &Png, new: &Png) -> Vec<Change> {
+    let (old_chunks, new_chunks) = (old.chunks(), new.chunks());
+    let mut partner: Vec<Option<usize>> = vec![None; old_chunks.len()];
+    let mut taken = vec![false; new_chunks.len()];
+    let passes: [fn(&Chunk, &Chunk) -> bool; 2] =
+        [same_chunk, |a, b| a.chunk_type() == b.chunk_type()];
+    for matches in passes.iter() {
+        for (i, chunk) in old_chunks.iter().enumerate() {
+            if partner[i].is_some() {
+                continue;
+            }
+            let found =
+                (0..new_chunks.len()).find(|&j| !taken[j] && matches(chunk, &new_chunks[j]));
+            if let Some(j) = found {
+                partner[i] = Some(j);
+                taken[j] = true;
+            }
+        }
+    }
+
+    let mut changes = Vec::new();
+    let pairs: Vec<(usize, usize)> = partner
+        .iter()
+        .enumerate()
+        .filter_map(|(i, j)| j.map(|j| (i, j)))
+        .collect();
+    let targets: Vec<usize> = pairs.iter().map(|&(_, j)| j).collect();
+    let in_order = longest_increasing(&targets);
+    for (k, &(i, j)) in pairs.iter().enumerate() {
+        let (before, after) = (&old_chunks[i], &new_chunks[j]);
+        if !in_order.contains(&k) {
+            changes.push(Change::Moved {
+                from: i,
+                to: j,
+                chunk_type: before.chunk_type().clone(),
+            });
+        }
+        if before.data() != after.data() {
+            changes.push(Change::Changed {
+                index: i,
+                chunk_type: before.chunk_type().clone(),
+                old_len: before.data().len(),
+                new_len: after.data().len(),
+                old_crc: before.crc(),
+                new_crc: after.crc(),
+            });
+        }
+    }
+    for (i, chunk) in old_chunks.iter().enumerate() {
+        if partner[i].is_none() {
+            changes.push(Change::Removed {
+                index: i,
+                chunk_type: chunk.chunk_type().clone(),
+                len: chunk.data().len(),
+            });
+        }
+    }
+    for (j, chunk) in new_chunks.iter().enumerate() {
+        if !taken[j] {
+            changes.push(Change::Added {
+                index: j,
+                chunk_type: chunk.chunk_type().clone(),
+                len: chunk.data().len(),
+            });
+        }
+    }
+    if old.trailer() != new.trailer() {
+        changes.push(Change::Trailer {
+            old_len: old.trailer().len(),
+            new_len: new.trailer().len(),
+        });
+    }
+    changes
+}
+
+/// Outcome of [`compare_pixels`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PixelDiff {
+    pub changed: usize,
+    pub total: usize,
+    /// Largest difference of a single channel, from 0 to 1.
+    pub max_difference: f64,
+    /// Smallest rectangle holding every changed pixel, as the top left and
+    /// bottom right corner.
+    pub bounds: Option<((u32, u32), (u32, u32))>,
+}
+
+impl fmt::Display for PixelDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bounds {
+            None => write!(f, "Pixels: all {} identical", self.total),
+            Some(((left, top), (right, bottom))) => write!(
+                f,
+                "Pixels: {} of {} differ by up to {:.1}%, between ({}, {}) and ({}, {})",
+                self.changed,
+                self.total,
+                self.max_difference * 100.0,
+                left,
+                top,
+                right,
+                bottom
+            ),
+        }
+    }
+}
+
+/// Every pixel as 16 bit RGBA. Palette images are expected to be expanded
+/// as by [`Png::image`]. `transparency` is the data of a tRNS chunk, the
+/// gray level or RGB color it holds becomes fully transparent.
+fn rgba(image: &Image, transparency: Option<&[u8]>) -> Vec<[u16; 4]> {
+    let bits = image.bit_depth as usize;
+    let max = (1u32 << bits) - 1;
+    let channels = image.channels();
+    let key: Option<Vec<u32>> = transparency.map(|data| {
+        data.chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as u32)
+            .collect()
+    });
+    let mut pixels = Vec::with_capacity(image.width as usize * image.height as usize);
+    for y in 0..image.height {
+        let row = image.row(y);
+        for x in 0..image.width as usize {
+            let raw = |channel: usize| {
+                let i = x * channels + channel;
+                match bits {
+                    16 => u16::from_be_bytes([row[i * 2], row[i * 2 + 1]]) as u32,
+                    8 => row[i] as u32,
+                    _ => read_bits(row, i * bits, bits) as u32,
+                }
+            };
+            let sample = |channel: usize| (raw(channel) * 0xffff / max) as u16;
+            let opacity = |samples: usize| match &key {
+                Some(key) if key.len() == samples && (0..samples).all(|c| raw(c) == key[c]) => 0,
+                _ => 0xffff,
+            };
+            pixels.push(match image.color_type {
+                ColorType::Grayscale => [sample(0), sample(0), sample(0), opacity(1)],
+                ColorType::Indexed => [sample(0), sample(0), sample(0), 0xffff],
+                ColorType::GrayscaleAlpha => [sample(0), sample(0), sample(0), sample(1)],
+                ColorType::Rgb => [sample(0), sample(1), sample(2), opacity(3)],
+                ColorType::Rgba => [sample(0), sample(1), sample(2), sample(3)],
+            });
+        }
+    }
+    pixels
+}
+
+/// Compares the decoded pixels of two images of the same size, whatever
+/// their color type, bit depth and way of storing transparency.
+pub fn compare_pixels(old: &Png, new: &Png) -> Result<PixelDiff> {
+    let trns = |png: &Png| png.chunk_by_type("tRNS").map(|chunk| chunk.data().to_vec());
+    let (old_trns, new_trns) = (trns(old), trns(new));
+    let (old, new) = (old.image()?, new.image()?);
+    if (old.width, old.height) != (new.width, new.height) {
+        return Err(Error::Custom("The images have different dimensions"));
+    }
+    let width = old.width as usize;
+    let mut diff = PixelDiff {
+        changed: 0,
+        total: width * old.height as usize,
+        max_difference: 0.0,
+        bounds: None,
+    };
+    let mut max_difference = 0;
+    let (old, new) = (
+        rgba(&old, old_trns.as_deref()),
+        rgba(&new, new_trns.as_deref()),
+    );
+    for (i, (a, b)) in old.iter().zip(new).enumerate() {
+        let difference = a
+            .iter()
+            .zip(&b)
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0);
+        if difference == 0 {
+            continue;
+        }
+        diff.changed += 1;
+        max_difference = max_difference.max(difference);
+        let (x, y) = ((i % width) as u32, (i / width) as u32);
+        diff.bounds = Some(match diff.bounds {
+            None => ((x, y), (x, y)),
+            Some(((left, top), (right, bottom))) => {
+                ((left.min(x), top.min(y)), (right.max(x), bottom.max(y)))
+            }
+        });
+    }
+    diff.max_difference = max_difference as f64 / 0xffff as f64;
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn chunk_type(chunk_type: &str) -> ChunkType {
+        ChunkType::from_str(chunk_type).unwrap()
+    }
+
+    #[test]
+    fn test_compare() {
+        let old = Png::from_chunks(vec![
+            chunk("IHDR", b"header"),
+            chunk("tEXt", b"Title\0Old"),
+            chunk("gAMA", b"gamma"),
+            chunk("IDAT", b"pixels"),
+            chunk("ruSt", b"secret"),
+            chunk("IEND", b""),
+        ]);
+        assert!(compare(&old, &old).is_empty());
+
+        let mut new = Png::from_chunks(vec![
+            chunk("IHDR", b"header"),
+            chunk("gAMA", b"gamma"),
+            chunk("tEXt", b"Title\0New!"),
+            chunk("IDAT", b"pixels"),
+            chunk("tIME", b"time"),
+            chunk("IEND", b""),
+        ]);
+        new.set_trailer(b"after".to_vec());
+        let changes = compare(&old, &new);
+        let crc = |chunk_type: &str, data: &[u8]| chunk(chunk_type, data).crc();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Changed {
+                    index: 1,
+                    chunk_type: chunk_type("tEXt"),
+                    old_len: 9,
+                    new_len: 10,
+                    old_crc: crc("tEXt", b"Title\0Old"),
+                    new_crc: crc("tEXt", b"Title\0New!"),
+                },
+                Change::Moved {
+                    from: 2,
+                    to: 1,
+                    chunk_type: chunk_type("gAMA"),
+                },
+                Change::Removed {
+                    index: 4,
+                    chunk_type: chunk_type("ruSt"),
+                    len: 6,
+                },
+                Change::Added {
+                    index: 4,
+                    chunk_type: chunk_type("tIME"),
+                    len: 4,
+                },
+                Change::Trailer {
+                    old_len: 0,
+                    new_len: 5,
+                },
+            ]
+        );
+        assert_eq!(changes[1].to_string(), "Moved gAMA from (3) to (2)");
+        assert_eq!(changes[2].to_string(), "Removed (5) ruSt, 6 bytes");
+    }
+
+    #[test]
+    fn test_longest_increasing() {
+        assert_eq!(longest_increasing(&[]), Vec::<usize>::new());
+        assert_eq!(longest_increasing(&[0, 2, 1, 3]), vec![0, 1, 3]);
+        assert_eq!(longest_increasing(&[4, 0, 1, 2, 3]), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_compare_pixels() {
+        let gray = Image::new(3, 2, ColorType::Grayscale, 8, vec![0, 64, 255, 10, 20, 30]).unwrap();
+        let rgb: Vec<u8> = gray.data.iter().flat_map(|&v| [v, v, v]).collect();
+        let mut rgb = Image::new(3, 2, ColorType::Rgb, 8, rgb).unwrap();
+        let png = |image: &Image| image.to_png(false).unwrap();
+        let same = compare_pixels(&png(&gray), &png(&rgb)).unwrap();
+        assert_eq!(same.changed, 0);
+        assert_eq!(same.to_string(), "Pixels: all 6 identical");
+
+        // Change the blue channel of (2, 0) and (1, 1).
+        rgb.data[2 * 3 + 2] = 0;
+        rgb.data[4 * 3 + 2] = 21;
+        let diff = compare_pixels(&png(&gray), &png(&rgb)).unwrap();
+        assert_eq!(diff.changed, 2);
+        assert_eq!(diff.max_difference, 1.0);
+        assert_eq!(diff.bounds, Some(((1, 0), (2, 1))));
+
+        // tRNS makes the gray level 64 at (1, 0) transparent.
+        let mut transparent = png(&gray);
+        transparent.insert_chunk(chunk("tRNS", &[0, 64]));
+        let diff = compare_pixels(&png(&gray), &transparent).unwrap();
+        assert_eq!(diff.changed, 1);
+        assert_eq!(diff.bounds, Some(((1, 0), (1, 0))));
+
+        let small = Image::new(1, 1, ColorType::Grayscale, 8, vec![0]).unwrap();
+        assert!(compare_pixels(&png(&gray), &png(&small)).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::str::FromStr;
+
+use crate::{chunk_type::ChunkType, png::Png, Error, Result};
+
+/// Private chunk types written by real image editors. Payload chunks named
+/// after them look like ordinary editor metadata.
+pub const VENDOR_CHUNK_TYPES: [&str; 14] = [
+    "mkBF", "mkBS", "mkBT", "mkTS", "prVW", // Macromedia Fireworks
+    "vpAg", "caNv", "orNT", // ImageMagick
+    "npTc", "npLb", "npOl", // Android nine-patch
+    "msOG", // Microsoft Office
+    "skMf", "skRf", // Skitch
+];
+
+/// The [`VENDOR_CHUNK_TYPES`] that are safe to copy. Editors keep those when
+/// the image changes, so only they are used for payloads.
+fn payload_vendor_types() -> Vec<&'static str> {
+    VENDOR_CHUNK_TYPES
+        .iter()
+        .copied()
+        .filter(|name| {
+            ChunkType::from_str(name)
+                .expect("vendor chunk types are valid")
+                .is_safe_to_copy()
+        })
+        .collect()
+}
+
+/// Letter pairs real vendors use as the start of their private chunk types.
+const VENDOR_PREFIXES: [&str; 16] = [
+    "mk", "vp", "ca", "np", "ms", "sk", "ad", "ap", "gi", "ps", "xm", "pr", "or", "cp", "ck", "fr",
+];
+
+fn key_hash(key: &str) -> u32 {
+    crc::crc32::checksum_ieee(key.as_bytes())
+}
+
+/// Derives an ancillary, private, safe-to-copy chunk type from `key`, so that
+/// the same key always finds the payload again.
+pub fn chunk_type_from_key(key: &str) -> ChunkType {
+    let hash = key_hash(key) as usize;
+    let prefix = VENDOR_PREFIXES[hash % VENDOR_PREFIXES.len()];
+    let third = (b'A' + (hash / 16 % 26) as u8) as char;
+    let fourth = (b'a' + (hash / 16 / 26 % 26) as u8) as char;
+    ChunkType::from_str(&format!("{}{}{}", prefix, third, fourth))
+        .expect("generated chunk types are four letters")
+}
+
+/// Picks one of the safe-to-copy [`VENDOR_CHUNK_TYPES`] based on `key`.
+pub fn vendor_chunk_type(key: &str) -> ChunkType {
+    let types = payload_vendor_types();
+    let index = key_hash(key) as usize % types.len();
+    ChunkType::from_str(types[index]).expect("vendor chunk types are valid")
+}
+
+/// Resolves a chunk type given on the command line to look up a payload.
+/// Besides a literal type it accepts `key:<secret>` for a type derived from a
+/// key and `vendor:<secret>` for a vendor type picked by a key. A bare
+/// `vendor` is refused: nothing tells a payload apart from a chunk a real
+/// editor wrote under the same type.
+pub fn resolve_chunk_type(spec: &str) -> Result<ChunkType> {
+    if spec == "vendor" {
+        return Err(Error::Custom(
+            "A bare vendor only picks a type for encode, give the chunk type it reported or vendor:<secret>",
+        ));
+    }
+    resolve_spec(spec)
+}
+
+/// Resolves a chunk type given on the command line for a new payload, like
+/// [`resolve_chunk_type`] except that a bare `vendor` picks the first vendor
+/// type not yet used in `png`.
+pub fn resolve_new_chunk_type(spec: &str, png: &Png) -> Result<ChunkType> {
+    if spec == "vendor" {
+        return payload_vendor_types()
+            .iter()
+            .find(|name| png.chunk_by_type(name).is_none())
+            .map(|name| ChunkType::from_str(name))
+            .unwrap_or(Err(Error::Custom(
+                "Every vendor chunk type is already used",
+            )));
+    }
+    resolve_spec(spec)
+}
+
+fn resolve_spec(spec: &str) -> Result<ChunkType> {
+    if let Some(key) = spec.strip_prefix("key:") {
+        return Ok(chunk_type_from_key(key));
+    }
+    if let Some(key) = spec.strip_prefix("vendor:") {
+        return Ok(vendor_chunk_type(key));
+    }
+    ChunkType::from_str(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, policy::check_payload_type};
+
+    #[test]
+    fn test_chunk_type_from_key() {
+        let chunk_type = chunk_type_from_key("hunter2");
+        assert_eq!(chunk_type, chunk_type_from_key("hunter2"));
+        assert_ne!(chunk_type, chunk_type_from_key("hunter3"));
+        for key in &["", "a", "hunter2", "correct horse battery staple"] {
+            let chunk_type = chunk_type_from_key(key);
+            assert!(check_payload_type(&chunk_type).is_ok(), "{}", chunk_type);
+            assert!(chunk_type.is_safe_to_copy());
+        }
+    }
+
+    #[test]
+    fn test_vendor_chunk_types() {
+        for name in VENDOR_CHUNK_TYPES.iter() {
+            let chunk_type = ChunkType::from_str(name).unwrap();
+            assert!(check_payload_type(&chunk_type).is_ok(), "{}", name);
+        }
+        assert_eq!(vendor_chunk_type("hunter2"), vendor_chunk_type("hunter2"));
+        for name in payload_vendor_types() {
+            assert!(
+                ChunkType::from_str(name).unwrap().is_safe_to_copy(),
+                "{}",
+                name
+            );
+        }
+        for key in &["", "a", "hunter2", "hunter3"] {
+            assert!(vendor_chunk_type(key).is_safe_to_copy());
+        }
+    }
+
+    #[test]
+    fn test_resolve_chunk_type() {
+        let mut png = Png::from_chunks(Vec::new());
+        let chunk_type = resolve_new_chunk_type("vendor", &png).unwrap();
+        assert_eq!(&chunk_type.to_string(), "vpAg");
+        // A chunk ImageMagick wrote is neither reused nor looked up.
+        png.append_chunk(Chunk::new(ChunkType::from_str("caNv").unwrap(), Vec::new()));
+        assert_eq!(resolve_new_chunk_type("vendor", &png).unwrap(), chunk_type);
+        png.append_chunk(Chunk::new(chunk_type, Vec::new()));
+        assert!(resolve_chunk_type("vendor").is_err());
+        let chunk_type = resolve_new_chunk_type("vendor", &png).unwrap();
+        assert_eq!(&chunk_type.to_string(), "npTc");
+
+        assert_eq!(
+            resolve_chunk_type("key:hunter2").unwrap(),
+            chunk_type_from_key("hunter2")
+        );
+        assert_eq!(&resolve_chunk_type("ruSt").unwrap().to_string(), "ruSt");
+        assert!(resolve_chunk_type("ruSty").is_err());
+    }
+}
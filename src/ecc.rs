@@ -0,0 +1,270 @@
+use crate::{Error, Result};
+
+/// Length of a Reed-Solomon code word over GF(256); the last block of a
+/// payload is shortened.
+const BLOCK_SIZE: usize = 255;
+
+/// Logarithm and exponent tables of GF(256) with the primitive polynomial
+/// x^8 + x^4 + x^3 + x^2 + 1. The exponent table is doubled so products of
+/// two logarithms need no reduction.
+const TABLES: ([u8; 512], [u8; 256]) = tables();
+const EXP: [u8; 512] = TABLES.0;
+const LOG: [u8; 256] = TABLES.1;
+
+const fn tables() -> ([u8; 512], [u8; 256]) {
+    let mut exp = [0; 512];
+    let mut log = [0; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11d;
+        }
+        i += 1;
+    }
+    while i < 512 {
+        exp[i] = exp[i - 255];
+        i += 1;
+    }
+    (exp, log)
+}
+
+fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        0
+    } else {
+        EXP[LOG[a as usize] as usize + LOG[b as usize] as usize]
+    }
+}
+
+fn div(a: u8, b: u8) -> u8 {
+    if a == 0 {
+        0
+    } else {
+        EXP[LOG[a as usize] as usize + 255 - LOG[b as usize] as usize]
+    }
+}
+
+/// α raised to `power`, which may be negative.
+fn alpha(power: isize) -> u8 {
+    EXP[power.rem_euclid(255) as usize]
+}
+
+/// Evaluates a polynomial with the lowest coefficient first.
+fn eval(poly: &[u8], x: u8) -> u8 {
+    poly.iter().rev().fold(0, |acc, &coef| mul(acc, x) ^ coef)
+}
+
+fn check_parity(parity: usize) -> Result<()> {
+    if (1..BLOCK_SIZE).contains(&parity) {
+        Ok(())
+    } else {
+        Err(Error::Custom(
+            "Error correction takes 1 to 254 parity bytes per block",
+        ))
+    }
+}
+
+/// Generator polynomial with the roots α^0 to α^(parity - 1), highest
+/// coefficient first.
+fn generator(parity: usize) -> Vec<u8> {
+    let mut poly = vec![1];
+    for i in 0..parity {
+        let root = alpha(i as isize);
+        let mut next = vec![0; poly.len() + 1];
+        for (j, &coef) in poly.iter().enumerate() {
+            next[j] ^= coef;
+            next[j + 1] ^= mul(coef, root);
+        }
+        poly = next;
+    }
+    poly
+}
+
+/// Syndromes of a code word whose first byte is the highest coefficient.
+fn syndromes(block: &[u8], parity: usize) -> Vec<u8> {
+    (0..parity)
+        .map(|i| {
+            let x = alpha(i as isize);
+            block.iter().fold(0, |acc, &byte| mul(acc, x) ^ byte)
+        })
+        .collect()
+}
+
+/// Appends `parity` bytes to every block of up to `255 - parity` bytes, so
+/// [`recover`] can repair up to `parity / 2` damaged bytes per block.
+pub fn protect(data: &[u8], parity: usize) -> Result<Vec<u8>> {
+    check_parity(parity)?;
+    let generator = generator(parity);
+    let mut out =
+        Vec::with_capacity(data.len() + data.len().div_ceil(BLOCK_SIZE - parity) * parity);
+    for block in data.chunks(BLOCK_SIZE - parity) {
+        // Remainder of the block times x^parity divided by the generator.
+        let mut remainder = block.to_vec();
+        remainder.resize(block.len() + parity, 0);
+        for i in 0..block.len() {
+            let coef = remainder[i];
+            if coef != 0 {
+                for (j, &g) in generator.iter().enumerate().skip(1) {
+                    remainder[i + j] ^= mul(g, coef);
+                }
+            }
+        }
+        out.extend_from_slice(block);
+        out.extend_from_slice(&remainder[block.len()..]);
+    }
+    Ok(out)
+}
+
+/// Corrects one code word in place and returns how many bytes were wrong.
+fn correct(block: &mut [u8], parity: usize) -> Result<usize> {
+    let syndromes = syndromes(block, parity);
+    if syndromes.iter().all(|&s| s == 0) {
+        return Ok(0);
+    }
+
+    // Berlekamp-Massey finds the error locator, lowest coefficient first.
+    let mut locator = vec![1];
+    let mut previous = vec![1];
+    let mut errors = 0;
+    let mut shift = 1;
+    let mut last_discrepancy = 1;
+    for n in 0..parity {
+        let discrepancy = (1..=errors).fold(syndromes[n], |acc, i| {
+            acc ^ mul(locator[i], syndromes[n - i])
+        });
+        if discrepancy == 0 {
+            shift += 1;
+            continue;
+        }
+        let scale = div(discrepancy, last_discrepancy);
+        let mut next = locator.clone();
+        next.resize(next.len().max(previous.len() + shift), 0);
+        for (i, &coef) in previous.iter().enumerate() {
+            next[i + shift] ^= mul(coef, scale);
+        }
+        if 2 * errors <= n {
+            previous = std::mem::replace(&mut locator, next);
+            errors = n + 1 - errors;
+            last_discrepancy = discrepancy;
+            shift = 1;
+        } else {
+            locator = next;
+            shift += 1;
+        }
+    }
+    if 2 * errors > parity {
+        return Err(Error::Custom("Too many damaged bytes to correct"));
+    }
+
+    // Chien search: byte p holds the coefficient of x^k with k = len - 1 - p
+    // and is wrong when α^-k is a root of the locator.
+    let len = block.len();
+    let positions: Vec<usize> = (0..len)
+        .filter(|&k| eval(&locator, alpha(-(k as isize))) == 0)
+        .collect();
+    if positions.len() != errors {
+        return Err(Error::Custom("Too many damaged bytes to correct"));
+    }
+
+    // Forney: the error value is X * Ω(X^-1) / Λ'(X^-1) for X = α^k.
+    let mut evaluator: Vec<u8> = vec![0; parity];
+    for (i, &s) in syndromes.iter().enumerate() {
+        for (j, &l) in locator.iter().enumerate().take(parity - i) {
+            evaluator[i + j] ^= mul(s, l);
+        }
+    }
+    let derivative: Vec<u8> = locator
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, &coef)| if i % 2 == 1 { coef } else { 0 })
+        .collect();
+    for &k in &positions {
+        let inverse = alpha(-(k as isize));
+        let value = mul(
+            alpha(k as isize),
+            div(eval(&evaluator, inverse), eval(&derivative, inverse)),
+        );
+        block[len - 1 - k] ^= value;
+    }
+    if syndromes_clear(block, parity) {
+        Ok(errors)
+    } else {
+        Err(Error::Custom("Too many damaged bytes to correct"))
+    }
+}
+
+fn syndromes_clear(block: &[u8], parity: usize) -> bool {
+    syndromes(block, parity).iter().all(|&s| s == 0)
+}
+
+/// Reverses [`protect`], repairing damaged bytes. Returns the data and how
+/// many bytes were corrected.
+pub fn recover(data: &[u8], parity: usize) -> Result<(Vec<u8>, usize)> {
+    check_parity(parity)?;
+    let mut out = Vec::with_capacity(data.len());
+    let mut corrected = 0;
+    for block in data.chunks(BLOCK_SIZE) {
+        if block.len() <= parity {
+            return Err(Error::Custom("Data is too short for the error correction"));
+        }
+        let mut block = block.to_vec();
+        corrected += correct(&mut block, parity)?;
+        out.extend_from_slice(&block[..block.len() - parity]);
+    }
+    Ok((out, corrected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 131 % 256) as u8).collect()
+    }
+
+    #[test]
+    fn test_field() {
+        assert_eq!(mul(2, 128), 0x1d);
+        assert_eq!(div(mul(57, 201), 201), 57);
+        assert_eq!(alpha(-1), div(1, 2));
+    }
+
+    #[test]
+    fn test_protect() {
+        let data = data(600);
+        let protected = protect(&data, 10).unwrap();
+        // Blocks of 245 bytes: 245 + 245 + 110.
+        assert_eq!(protected.len(), 600 + 3 * 10);
+        assert_eq!(&protected[..245], &data[..245]);
+        assert_eq!(recover(&protected, 10).unwrap(), (data, 0));
+        assert!(protect(&[1], 0).is_err());
+        assert!(protect(&[1], 255).is_err());
+    }
+
+    #[test]
+    fn test_recover() {
+        let data = data(400);
+        let mut damaged = protect(&data, 16).unwrap();
+        // Eight errors in the full block, some in the parity bytes.
+        for &pos in &[0, 1, 50, 100, 200, 238, 240, 254] {
+            damaged[pos] ^= 0x5a;
+        }
+        // Three in the shortened block.
+        for &pos in &[255, 300, 411] {
+            damaged[pos] = !damaged[pos];
+        }
+        assert_eq!(recover(&damaged, 16).unwrap(), (data.clone(), 11));
+
+        let mut lost = protect(&data, 4).unwrap();
+        for byte in &mut lost[10..20] {
+            *byte = 0;
+        }
+        assert!(recover(&lost, 4).map_or(true, |(out, _)| out != data));
+        assert!(recover(&lost[..3], 4).is_err());
+    }
+}
@@ -12,6 +12,15 @@ pub enum Error<'a> {
     Utf8Err(std::str::Utf8Error),
     FromUtf8Error(std::string::FromUtf8Error),
     FromSlice(std::array::TryFromSliceError),
+    #[cfg(feature = "serde")]
+    Json(serde_json::Error),
+}
+
+#[cfg(feature = "serde")]
+impl<'a> From<serde_json::Error> for Error<'a> {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
 }
 
 impl<'a> From<std::array::TryFromSliceError> for Error<'a> {
@@ -66,6 +75,8 @@ impl<'a> fmt::Display for Error<'a> {
             Self::Fmt(e) => write!(f, "{}", e),
             Self::Utf8Err(e) => write!(f, "{}", e),
             Self::FromUtf8Error(e) => write!(f, "{}", e),
+            #[cfg(feature = "serde")]
+            Self::Json(e) => write!(f, "{}", e),
         }
     }
 }
@@ -0,0 +1,511 @@
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::fmt;
+
+use crate::{meta::Metadata, Error, Result};
+
+const EXIF_POINTER: u16 = 0x8769;
+const GPS_POINTER: u16 = 0x8825;
+const INTEROP_POINTER: u16 = 0xa005;
+
+/// Tags that identify the camera, lens or owner.
+const SERIAL_TAGS: [u16; 5] = [0xa420, 0xa430, 0xa431, 0xa435, 0xc62f];
+
+/// Image file directory an entry belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ifd {
+    Primary,
+    Thumbnail,
+    Exif,
+    Gps,
+    Interop,
+}
+
+impl fmt::Display for Ifd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Ifd::Primary => "IFD0",
+            Ifd::Thumbnail => "IFD1",
+            Ifd::Exif => "Exif",
+            Ifd::Gps => "GPS",
+            Ifd::Interop => "Interop",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Single directory entry of the TIFF structure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub ifd: Ifd,
+    pub tag: u16,
+    pub field_type: u16,
+    pub count: u32,
+    /// Offset of the directory the entry is stored in.
+    dir_offset: usize,
+    /// Offset of the value, which is inside the entry itself if it fits in four bytes.
+    value_offset: usize,
+}
+
+impl Entry {
+    pub fn name(&self) -> Option<&'static str> {
+        tag_name(self.ifd, self.tag)
+    }
+
+    /// Whether the entry is selected by `tag`, given as a name like `Make` or
+    /// as a number, decimal or 0x-prefixed hex.
+    pub fn matches(&self, tag: &str) -> bool {
+        let number = match tag.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16).ok(),
+            None => tag.parse().ok(),
+        };
+        match number {
+            Some(number) => self.tag == number,
+            None => self.name() == Some(tag),
+        }
+    }
+
+    fn value_size(&self) -> usize {
+        let unit = match self.field_type {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 => 4,
+            5 | 10 | 12 => 8,
+            _ => 0,
+        };
+        unit * self.count as usize
+    }
+
+    fn is_inline(&self) -> bool {
+        self.value_size() <= 4
+    }
+}
+
+/// Contents of an `eXIf` chunk: a TIFF header followed by image file directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exif {
+    data: Vec<u8>,
+    big_endian: bool,
+    entries: Vec<Entry>,
+}
+
+impl Exif {
+    pub fn parse(data: &[u8]) -> Result<Exif> {
+        let big_endian = match data.get(0..2) {
+            Some(b"MM") => true,
+            Some(b"II") => false,
+            _ => return Err(Error::Custom("Invalid TIFF byte order")),
+        };
+        let mut exif = Exif {
+            data: data.to_vec(),
+            big_endian,
+            entries: Vec::new(),
+        };
+        if exif.u16_at(2)? != 42 {
+            return Err(Error::Custom("Invalid TIFF magic number"));
+        }
+
+        let mut visited = HashSet::new();
+        let first = exif.u32_at(4)? as usize;
+        let next = exif.read_ifd(first, Ifd::Primary, &mut visited)?;
+        if next != 0 {
+            exif.read_ifd(next, Ifd::Thumbnail, &mut visited)?;
+        }
+        Ok(exif)
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Reads the directory at `offset` and every sub-directory it points to.
+    /// Returns the offset of the next directory in the chain.
+    fn read_ifd(&mut self, offset: usize, ifd: Ifd, visited: &mut HashSet<usize>) -> Result<usize> {
+        if !visited.insert(offset) {
+            return Err(Error::Custom("Loop in EXIF directories"));
+        }
+        let count = self.u16_at(offset)? as usize;
+        for i in 0..count {
+            let at = offset + 2 + i * 12;
+            let mut entry = Entry {
+                ifd,
+                tag: self.u16_at(at)?,
+                field_type: self.u16_at(at + 2)?,
+                count: self.u32_at(at + 4)?,
+                dir_offset: offset,
+                value_offset: at + 8,
+            };
+            if !entry.is_inline() {
+                entry.value_offset = self.u32_at(at + 8)? as usize;
+            }
+            if entry.value_offset + entry.value_size() > self.data.len() {
+                return Err(Error::Custom("EXIF value is out of bounds"));
+            }
+            let sub_ifd = match entry.tag {
+                EXIF_POINTER if ifd == Ifd::Primary => Some(Ifd::Exif),
+                GPS_POINTER if ifd == Ifd::Primary => Some(Ifd::Gps),
+                INTEROP_POINTER if ifd == Ifd::Exif => Some(Ifd::Interop),
+                _ => None,
+            };
+            let pointer = self.u32_at(at + 8)? as usize;
+            self.entries.push(entry);
+            if let Some(sub_ifd) = sub_ifd {
+                self.read_ifd(pointer, sub_ifd, visited)?;
+            }
+        }
+        Ok(self.u32_at(offset + 2 + count * 12)? as usize)
+    }
+
+    fn u16_at(&self, offset: usize) -> Result<u16> {
+        let bytes: [u8; 2] = self
+            .data
+            .get(offset..offset + 2)
+            .ok_or(Error::Custom("EXIF data is truncated"))?
+            .try_into()?;
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32_at(&self, offset: usize) -> Result<u32> {
+        let bytes: [u8; 4] = self
+            .data
+            .get(offset..offset + 4)
+            .ok_or(Error::Custom("EXIF data is truncated"))?
+            .try_into()?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn write_u16(&mut self, offset: usize, value: u16) {
+        let bytes = if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.data[offset..offset + 2].copy_from_slice(&bytes);
+    }
+
+    /// Human readable value of an entry, shortened for long arrays.
+    pub fn value(&self, entry: &Entry) -> String {
+        const MAX_VALUES: usize = 8;
+
+        let at = entry.value_offset;
+        let count = entry.count as usize;
+        let shown = count.min(MAX_VALUES);
+        let mut values: Vec<String> = match entry.field_type {
+            2 => {
+                let bytes = &self.data[at..at + count];
+                let text = bytes.split(|&b| b == 0).next().unwrap_or_default();
+                return String::from_utf8_lossy(text).into_owned();
+            }
+            3 => (0..shown)
+                .filter_map(|i| self.u16_at(at + i * 2).ok())
+                .map(|v| v.to_string())
+                .collect(),
+            4 => (0..shown)
+                .filter_map(|i| self.u32_at(at + i * 4).ok())
+                .map(|v| v.to_string())
+                .collect(),
+            5 | 10 => (0..shown)
+                .filter_map(|i| {
+                    let num = self.u32_at(at + i * 8).ok()?;
+                    let den = self.u32_at(at + i * 8 + 4).ok()?;
+                    Some(if entry.field_type == 10 {
+                        format!("{}/{}", num as i32, den as i32)
+                    } else {
+                        format!("{}/{}", num, den)
+                    })
+                })
+                .collect(),
+            1 | 7 if count <= 16 => self.data[at..at + count]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+            _ => return format!("{} bytes", entry.value_size()),
+        };
+        if count > shown {
+            values.push("...".to_string());
+        }
+        values.join(", ")
+    }
+
+    /// Removes every entry matching the predicate, wiping its value and, for
+    /// pointer tags, the whole directory it points to. Returns the number of
+    /// entries removed. Offsets of all other data stay the same.
+    pub fn redact<F: Fn(&Entry) -> bool>(&mut self, predicate: F) -> Result<usize> {
+        let mut removed = 0;
+        while let Some(entry) = self.entries.iter().find(|e| predicate(e)).cloned() {
+            removed += self.remove_entry(&entry)?;
+        }
+        Ok(removed)
+    }
+
+    /// Removes the GPS directory and the pointer to it.
+    pub fn redact_gps(&mut self) -> Result<usize> {
+        self.redact(|e| e.ifd == Ifd::Primary && e.tag == GPS_POINTER)
+    }
+
+    /// Removes serial numbers and other tags identifying the camera or owner.
+    pub fn redact_serials(&mut self) -> Result<usize> {
+        self.redact(|e| e.ifd != Ifd::Gps && SERIAL_TAGS.contains(&e.tag))
+    }
+
+    fn remove_entry(&mut self, entry: &Entry) -> Result<usize> {
+        let mut removed = 1;
+        let sub_ifd = match (entry.ifd, entry.tag) {
+            (Ifd::Primary, EXIF_POINTER) => Some(Ifd::Exif),
+            (Ifd::Primary, GPS_POINTER) => Some(Ifd::Gps),
+            (Ifd::Exif, INTEROP_POINTER) => Some(Ifd::Interop),
+            _ => None,
+        };
+        if let Some(sub_ifd) = sub_ifd {
+            let children: Vec<Entry> = self
+                .entries
+                .iter()
+                .filter(|e| e.ifd == sub_ifd)
+                .cloned()
+                .collect();
+            for child in children.iter().rev() {
+                removed += self.remove_entry(child)?;
+            }
+            // The now empty directory is just a count and a next pointer.
+            let dir = self.u32_at(entry.value_offset)? as usize;
+            self.data[dir..dir + 6].iter_mut().for_each(|b| *b = 0);
+        }
+
+        if !entry.is_inline() {
+            let range = entry.value_offset..entry.value_offset + entry.value_size();
+            self.data[range].iter_mut().for_each(|b| *b = 0);
+        }
+
+        // Close the gap in the directory and clear the freed slot at its end.
+        let dir = entry.dir_offset;
+        let count = self.u16_at(dir)? as usize;
+        let position = self
+            .entries
+            .iter()
+            .filter(|e| e.dir_offset == dir)
+            .position(|e| e == entry)
+            .ok_or(Error::Custom("EXIF entry not found"))?;
+        let start = dir + 2 + position * 12;
+        let end = dir + 2 + count * 12 + 4;
+        self.data.copy_within(start + 12..end, start);
+        self.data[end - 12..end].iter_mut().for_each(|b| *b = 0);
+        self.write_u16(dir, (count - 1) as u16);
+
+        *self = Exif::parse(&self.data)?;
+        Ok(removed)
+    }
+}
+
+impl Metadata for Exif {
+    const CHUNK_TYPE: &'static str = "eXIf";
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        Exif::parse(data)
+    }
+
+    fn to_data(&self) -> Result<Vec<u8>> {
+        Ok(self.data.clone())
+    }
+}
+
+impl fmt::Display for Exif {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} tags", self.entries.len())?;
+        for entry in &self.entries {
+            write!(f, "\n    [{}] ", entry.ifd)?;
+            match entry.name() {
+                Some(name) => write!(f, "{}", name)?,
+                None => write!(f, "0x{:04x}", entry.tag)?,
+            }
+            write!(f, ": {}", self.value(entry))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn tag_name(ifd: Ifd, tag: u16) -> Option<&'static str> {
+    if ifd == Ifd::Gps {
+        return match tag {
+            0x00 => Some("GPSVersionID"),
+            0x01 => Some("GPSLatitudeRef"),
+            0x02 => Some("GPSLatitude"),
+            0x03 => Some("GPSLongitudeRef"),
+            0x04 => Some("GPSLongitude"),
+            0x05 => Some("GPSAltitudeRef"),
+            0x06 => Some("GPSAltitude"),
+            0x07 => Some("GPSTimeStamp"),
+            0x08 => Some("GPSSatellites"),
+            0x10 => Some("GPSImgDirectionRef"),
+            0x11 => Some("GPSImgDirection"),
+            0x12 => Some("GPSMapDatum"),
+            0x1b => Some("GPSProcessingMethod"),
+            0x1d => Some("GPSDateStamp"),
+            _ => None,
+        };
+    }
+    match tag {
+        0x010e => Some("ImageDescription"),
+        0x010f => Some("Make"),
+        0x0110 => Some("Model"),
+        0x0112 => Some("Orientation"),
+        0x011a => Some("XResolution"),
+        0x011b => Some("YResolution"),
+        0x0128 => Some("ResolutionUnit"),
+        0x0131 => Some("Software"),
+        0x0132 => Some("DateTime"),
+        0x013b => Some("Artist"),
+        0x0201 => Some("JPEGInterchangeFormat"),
+        0x0202 => Some("JPEGInterchangeFormatLength"),
+        0x0213 => Some("YCbCrPositioning"),
+        0x8298 => Some("Copyright"),
+        0x829a => Some("ExposureTime"),
+        0x829d => Some("FNumber"),
+        0x8769 => Some("ExifIFDPointer"),
+        0x8822 => Some("ExposureProgram"),
+        0x8825 => Some("GPSInfo"),
+        0x8827 => Some("ISOSpeedRatings"),
+        0x9000 => Some("ExifVersion"),
+        0x9003 => Some("DateTimeOriginal"),
+        0x9004 => Some("DateTimeDigitized"),
+        0x9201 => Some("ShutterSpeedValue"),
+        0x9202 => Some("ApertureValue"),
+        0x9209 => Some("Flash"),
+        0x920a => Some("FocalLength"),
+        0x927c => Some("MakerNote"),
+        0x9286 => Some("UserComment"),
+        0xa001 => Some("ColorSpace"),
+        0xa002 => Some("PixelXDimension"),
+        0xa003 => Some("PixelYDimension"),
+        0xa005 => Some("InteroperabilityIFDPointer"),
+        0xa420 => Some("ImageUniqueID"),
+        0xa430 => Some("CameraOwnerName"),
+        0xa431 => Some("BodySerialNumber"),
+        0xa432 => Some("LensSpecification"),
+        0xa433 => Some("LensMake"),
+        0xa434 => Some("LensModel"),
+        0xa435 => Some("LensSerialNumber"),
+        0xc62f => Some("CameraSerialNumber"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Little endian TIFF with Make in IFD0, a body serial number in the Exif
+    /// directory and a latitude in the GPS directory.
+    pub(crate) fn testing_exif() -> Vec<u8> {
+        let mut data = b"II".to_vec();
+        data.extend(&42u16.to_le_bytes());
+        data.extend(&8u32.to_le_bytes());
+
+        let entry = |data: &mut Vec<u8>, tag: u16, field_type: u16, count: u32, value: u32| {
+            data.extend(&tag.to_le_bytes());
+            data.extend(&field_type.to_le_bytes());
+            data.extend(&count.to_le_bytes());
+            data.extend(&value.to_le_bytes());
+        };
+
+        // IFD0 at 8
+        data.extend(&3u16.to_le_bytes());
+        entry(&mut data, 0x010f, 2, 6, 50);
+        entry(&mut data, EXIF_POINTER, 4, 1, 56);
+        entry(&mut data, GPS_POINTER, 4, 1, 84);
+        data.extend(&0u32.to_le_bytes());
+        data.extend(b"Canon\0");
+        // Exif IFD at 56
+        data.extend(&1u16.to_le_bytes());
+        entry(&mut data, 0xa431, 2, 9, 74);
+        data.extend(&0u32.to_le_bytes());
+        data.extend(b"12345678\0\0");
+        // GPS IFD at 84
+        data.extend(&2u16.to_le_bytes());
+        entry(&mut data, 0x01, 2, 2, u32::from_le_bytes(*b"N\0\0\0"));
+        entry(&mut data, 0x02, 5, 3, 114);
+        data.extend(&0u32.to_le_bytes());
+        for &(num, den) in &[(51u32, 1u32), (30, 1), (2634, 100)] {
+            data.extend(&num.to_le_bytes());
+            data.extend(&den.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse() {
+        let exif = Exif::parse(&testing_exif()).unwrap();
+        let names: Vec<&str> = exif.entries().iter().filter_map(|e| e.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "Make",
+                "ExifIFDPointer",
+                "BodySerialNumber",
+                "GPSInfo",
+                "GPSLatitudeRef",
+                "GPSLatitude"
+            ]
+        );
+        assert_eq!(exif.value(&exif.entries()[0]), "Canon");
+        assert_eq!(exif.value(&exif.entries()[5]), "51/1, 30/1, 2634/100");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Exif::parse(b"XX\x2a\x00").is_err());
+        let mut data = testing_exif();
+        data.truncate(100);
+        assert!(Exif::parse(&data).is_err());
+    }
+
+    #[test]
+    fn test_redact_gps() {
+        let original = testing_exif();
+        let mut exif = Exif::parse(&original).unwrap();
+        assert_eq!(exif.redact_gps().unwrap(), 3);
+        assert!(exif.entries().iter().all(|e| e.ifd != Ifd::Gps));
+        assert_eq!(exif.entries().len(), 3);
+        assert_eq!(exif.as_bytes().len(), original.len());
+        assert!(exif.as_bytes()[84..].iter().all(|&b| b == 0));
+        assert_eq!(exif.value(&exif.entries()[0]), "Canon");
+    }
+
+    #[test]
+    fn test_redact_serials() {
+        let mut exif = Exif::parse(&testing_exif()).unwrap();
+        assert_eq!(exif.redact_serials().unwrap(), 1);
+        assert!(exif.entries().iter().all(|e| e.tag != 0xa431));
+        assert!(!exif.as_bytes().windows(8).any(|w| w == b"12345678"));
+        assert_eq!(exif.redact_serials().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_redact_by_name() {
+        let mut exif = Exif::parse(&testing_exif()).unwrap();
+        assert_eq!(exif.redact(|e| e.matches("GPSLatitude")).unwrap(), 1);
+        assert_eq!(exif.redact(|e| e.matches("0x010f")).unwrap(), 1);
+        assert_eq!(exif.redact(|e| e.matches("Nonsense")).unwrap(), 0);
+        let names: Vec<&str> = exif.entries().iter().filter_map(|e| e.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "ExifIFDPointer",
+                "BodySerialNumber",
+                "GPSInfo",
+                "GPSLatitudeRef"
+            ]
+        );
+    }
+}
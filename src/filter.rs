@@ -0,0 +1,251 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{ihdr::Ihdr, Error, Result};
+
+/// Per-scanline filters of PNG filter method 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    None,
+    Sub,
+    Up,
+    Average,
+    Paeth,
+}
+
+impl FilterType {
+    const ALL: [FilterType; 5] = [
+        FilterType::None,
+        FilterType::Sub,
+        FilterType::Up,
+        FilterType::Average,
+        FilterType::Paeth,
+    ];
+}
+
+impl TryFrom<u8> for FilterType {
+    type Error = Error<'static>;
+
+    fn try_from(code: u8) -> Result<Self> {
+        FilterType::ALL
+            .get(code as usize)
+            .copied()
+            .ok_or(Error::Custom("Unknown filter type"))
+    }
+}
+
+/// How [`filter`] picks the filter of every scanline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// The same filter for every scanline.
+    Fixed(FilterType),
+    /// The filter with the smallest sum of absolute differences per
+    /// scanline, the heuristic the PNG specification recommends.
+    Adaptive,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 6] = [
+        Strategy::Fixed(FilterType::None),
+        Strategy::Fixed(FilterType::Sub),
+        Strategy::Fixed(FilterType::Up),
+        Strategy::Fixed(FilterType::Average),
+        Strategy::Fixed(FilterType::Paeth),
+        Strategy::Adaptive,
+    ];
+}
+
+impl FromStr for Strategy {
+    type Err = Error<'static>;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Strategy::ALL
+            .iter()
+            .find(|strategy| strategy.to_string() == s)
+            .copied()
+            .ok_or(Error::Custom(
+                "Expected none, sub, up, average, paeth or adaptive",
+            ))
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Strategy::Fixed(FilterType::None) => "none",
+            Strategy::Fixed(FilterType::Sub) => "sub",
+            Strategy::Fixed(FilterType::Up) => "up",
+            Strategy::Fixed(FilterType::Average) => "average",
+            Strategy::Fixed(FilterType::Paeth) => "paeth",
+            Strategy::Adaptive => "adaptive",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Value the filter predicts for `row[i]` from the bytes to the left (`a`),
+/// above (`b`) and above left (`c`), which are 0 outside the image.
+fn predict(filter: FilterType, row: &[u8], previous: &[u8], i: usize, distance: usize) -> u8 {
+    let a = if i >= distance { row[i - distance] } else { 0 };
+    let b = previous[i];
+    let c = if i >= distance {
+        previous[i - distance]
+    } else {
+        0
+    };
+    match filter {
+        FilterType::None => 0,
+        FilterType::Sub => a,
+        FilterType::Up => b,
+        FilterType::Average => ((a as u16 + b as u16) / 2) as u8,
+        FilterType::Paeth => paeth(a, b, c),
+    }
+}
+
+fn filter_row(filter: FilterType, row: &[u8], previous: &[u8], distance: usize, out: &mut Vec<u8>) {
+    out.push(filter as u8);
+    out.extend(
+        (0..row.len()).map(|i| row[i].wrapping_sub(predict(filter, row, previous, i, distance))),
+    );
+}
+
+/// Reverses the filters of decompressed image data, returning the raw
+/// scanlines of every pass without their filter type bytes.
+pub fn unfilter(data: &[u8], ihdr: &Ihdr) -> Result<Vec<u8>> {
+    let distance = ihdr.filter_distance();
+    let mut out = Vec::with_capacity(data.len());
+    let mut pos = 0;
+    for pass in ihdr.passes() {
+        let row_bytes = ihdr.row_bytes(pass.width);
+        let mut previous = vec![0; row_bytes];
+        for _ in 0..pass.height {
+            let line = data
+                .get(pos..pos + 1 + row_bytes)
+                .ok_or(Error::Custom("Image data is shorter than the image"))?;
+            pos += 1 + row_bytes;
+            let filter = FilterType::try_from(line[0])?;
+            let mut row = line[1..].to_vec();
+            for i in 0..row_bytes {
+                row[i] = row[i].wrapping_add(predict(filter, &row, &previous, i, distance));
+            }
+            out.extend_from_slice(&row);
+            previous = row;
+        }
+    }
+    Ok(out)
+}
+
+/// Filters raw scanlines as returned by [`unfilter`], ready to be compressed.
+pub fn filter(raw: &[u8], ihdr: &Ihdr, strategy: Strategy) -> Vec<u8> {
+    let distance = ihdr.filter_distance();
+    let mut out = Vec::with_capacity(raw.len() + raw.len() / 64);
+    let mut candidate = Vec::new();
+    let mut best = Vec::new();
+    let mut pos = 0;
+    for pass in ihdr.passes() {
+        let row_bytes = ihdr.row_bytes(pass.width);
+        let zeros = vec![0; row_bytes];
+        let mut previous = &zeros[..];
+        for _ in 0..pass.height {
+            let row = &raw[pos..pos + row_bytes];
+            pos += row_bytes;
+            match strategy {
+                Strategy::Fixed(filter) => filter_row(filter, row, previous, distance, &mut out),
+                Strategy::Adaptive => {
+                    let mut best_score = u64::MAX;
+                    for &filter in FilterType::ALL.iter() {
+                        candidate.clear();
+                        filter_row(filter, row, previous, distance, &mut candidate);
+                        let score = candidate[1..]
+                            .iter()
+                            .map(|&b| (b as i8).unsigned_abs() as u64)
+                            .sum();
+                        if score < best_score {
+                            best_score = score;
+                            std::mem::swap(&mut best, &mut candidate);
+                        }
+                    }
+                    out.extend_from_slice(&best);
+                }
+            }
+            previous = row;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::ColorType;
+
+    fn ihdr(width: u32, height: u32, color_type: ColorType, interlace_method: u8) -> Ihdr {
+        Ihdr {
+            width,
+            height,
+            bit_depth: 8,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method,
+        }
+    }
+
+    fn gradient(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for &interlace in &[0, 1] {
+            let ihdr = ihdr(13, 9, ColorType::Rgb, interlace);
+            let raw = gradient(13 * 9 * 3);
+            for &strategy in Strategy::ALL.iter() {
+                let filtered = filter(&raw, &ihdr, strategy);
+                assert_eq!(unfilter(&filtered, &ihdr).unwrap(), raw, "{}", strategy);
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_rows() {
+        let ihdr = ihdr(2, 2, ColorType::Grayscale, 0);
+        let raw = [10, 20, 30, 50];
+        let sub = filter(&raw, &ihdr, Strategy::Fixed(FilterType::Sub));
+        assert_eq!(sub, vec![1, 10, 10, 1, 30, 20]);
+        let up = filter(&raw, &ihdr, Strategy::Fixed(FilterType::Up));
+        assert_eq!(up, vec![2, 10, 20, 2, 20, 30]);
+    }
+
+    #[test]
+    fn test_unfilter_invalid() {
+        let ihdr = ihdr(2, 2, ColorType::Grayscale, 0);
+        assert!(unfilter(&[0, 1, 2, 0, 3], &ihdr).is_err());
+        assert!(unfilter(&[5, 1, 2, 0, 3, 4], &ihdr).is_err());
+    }
+
+    #[test]
+    fn test_strategy_from_str() {
+        assert_eq!(
+            Strategy::from_str("paeth").unwrap(),
+            Strategy::Fixed(FilterType::Paeth)
+        );
+        assert_eq!(Strategy::from_str("adaptive").unwrap(), Strategy::Adaptive);
+        assert!(Strategy::from_str("best").is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use std::convert::TryFrom;
+use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    /// Number of samples stored for every pixel.
+    pub fn channels(self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    pub fn code(self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = Error<'static>;
+
+    fn try_from(code: u8) -> Result<Self> {
+        match code {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(Error::Custom("Unknown color type")),
+        }
+    }
+}
+
+impl fmt::Display for ColorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "grayscale",
+            ColorType::Rgb => "RGB",
+            ColorType::Indexed => "indexed",
+            ColorType::GrayscaleAlpha => "grayscale with alpha",
+            ColorType::Rgba => "RGBA",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Image header, the mandatory first chunk of every PNG file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: u8,
+}
+
+/// One of the reduced images the scanlines of a PNG are stored as: the whole
+/// image, or one of the seven Adam7 passes of an interlaced image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pass {
+    /// Position of the first pixel of the pass in the full image.
+    pub x: u32,
+    pub y: u32,
+    /// Distance between the pixels of the pass in the full image.
+    pub dx: u32,
+    pub dy: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Start and step of the Adam7 passes as `(x, y, dx, dy)`.
+const ADAM7: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+impl Ihdr {
+    const LENGTH: usize = 13;
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(Ihdr::LENGTH);
+        data.extend_from_slice(&self.width.to_be_bytes());
+        data.extend_from_slice(&self.height.to_be_bytes());
+        data.extend_from_slice(&[
+            self.bit_depth,
+            self.color_type.code(),
+            self.compression_method,
+            self.filter_method,
+            self.interlace_method,
+        ]);
+        Chunk::new(
+            ChunkType::from_str("IHDR").expect("IHDR is a valid chunk type"),
+            data,
+        )
+    }
+
+    pub fn is_interlaced(&self) -> bool {
+        self.interlace_method == 1
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.bit_depth as usize
+    }
+
+    /// Distance in bytes to the matching byte of the previous pixel, which
+    /// filters predict from. Pixels smaller than a byte use 1.
+    pub fn filter_distance(&self) -> usize {
+        (self.bits_per_pixel() / 8).max(1)
+    }
+
+    /// Bytes in a scanline of `width` pixels, not counting the filter type.
+    pub fn row_bytes(&self, width: u32) -> usize {
+        (width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// Reduced images in the order their scanlines appear in the image data.
+    /// Empty Adam7 passes store no scanlines and are left out.
+    pub fn passes(&self) -> Vec<Pass> {
+        if !self.is_interlaced() {
+            return vec![Pass {
+                x: 0,
+                y: 0,
+                dx: 1,
+                dy: 1,
+                width: self.width,
+                height: self.height,
+            }];
+        }
+        ADAM7
+            .iter()
+            .map(|&(x, y, dx, dy)| Pass {
+                x,
+                y,
+                dx,
+                dy,
+                width: (self.width + dx - 1 - x) / dx,
+                height: (self.height + dy - 1 - y) / dy,
+            })
+            .filter(|pass| pass.width > 0 && pass.height > 0)
+            .collect()
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = Error<'static>;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if chunk.chunk_type().bytes() != b"IHDR" {
+            return Err(Error::Custom("Chunk is not an IHDR"));
+        }
+        let data = chunk.data();
+        if data.len() != Ihdr::LENGTH {
+            return Err(Error::Custom("IHDR chunk has an invalid length"));
+        }
+        Ok(Ihdr {
+            width: u32::from_be_bytes(data[0..4].try_into()?),
+            height: u32::from_be_bytes(data[4..8].try_into()?),
+            bit_depth: data[8],
+            color_type: ColorType::try_from(data[9])?,
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        })
+    }
+}
+
+impl fmt::Display for Ihdr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "  Width: {}", self.width)?;
+        writeln!(f, "  Height: {}", self.height)?;
+        writeln!(f, "  Bit depth: {}", self.bit_depth)?;
+        writeln!(f, "  Color type: {}", self.color_type)?;
+        writeln!(f, "  Compression method: {}", self.compression_method)?;
+        writeln!(f, "  Filter method: {}", self.filter_method)?;
+        let interlace = match self.interlace_method {
+            0 => "none",
+            1 => "Adam7",
+            _ => "unknown",
+        };
+        writeln!(f, "  Interlace: {}", interlace)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr_chunk(data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str("IHDR").unwrap(), data)
+    }
+
+    #[test]
+    fn test_ihdr_from_chunk() {
+        let chunk = ihdr_chunk(vec![0, 0, 0, 50, 0, 0, 0, 40, 8, 6, 0, 0, 1]);
+        let ihdr = Ihdr::try_from(&chunk).unwrap();
+        assert_eq!(ihdr.width, 50);
+        assert_eq!(ihdr.height, 40);
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, ColorType::Rgba);
+        assert_eq!(ihdr.interlace_method, 1);
+        assert_eq!(ihdr.to_chunk().as_bytes(), chunk.as_bytes());
+    }
+
+    #[test]
+    fn test_passes() {
+        let chunk = ihdr_chunk(vec![0, 0, 0, 10, 0, 0, 0, 3, 2, 3, 0, 0, 0]);
+        let mut ihdr = Ihdr::try_from(&chunk).unwrap();
+        assert_eq!(ihdr.bits_per_pixel(), 2);
+        assert_eq!(ihdr.filter_distance(), 1);
+        assert_eq!(ihdr.row_bytes(10), 3);
+        assert_eq!(ihdr.passes().len(), 1);
+
+        ihdr.interlace_method = 1;
+        let sizes: Vec<(u32, u32)> = ihdr
+            .passes()
+            .iter()
+            .map(|pass| (pass.width, pass.height))
+            .collect();
+        // The third pass starts at row 4 and is empty in a 3 rows high image.
+        assert_eq!(sizes, vec![(2, 1), (1, 1), (2, 1), (5, 1), (5, 2), (10, 1)]);
+        let pixels: u32 = sizes.iter().map(|(w, h)| w * h).sum();
+        assert_eq!(pixels, 10 * 3);
+    }
+
+    #[test]
+    fn test_ihdr_invalid() {
+        let chunk = ihdr_chunk(vec![0, 0, 0, 50]);
+        assert!(Ihdr::try_from(&chunk).is_err());
+
+        let chunk = ihdr_chunk(vec![0, 0, 0, 50, 0, 0, 0, 40, 8, 5, 0, 0, 1]);
+        assert!(Ihdr::try_from(&chunk).is_err());
+    }
+}
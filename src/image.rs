@@ -0,0 +1,281 @@
+use std::str::FromStr;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    filter::{filter, FilterType, Strategy},
+    ihdr::{ColorType, Ihdr},
+    png::{Png, IDAT_SIZE},
+    zlib, Error, Result,
+};
+
+/// Decoded pixels of a PNG. Rows are stored one after another without filter
+/// type bytes and samples keep the PNG layout: pixels smaller than a byte are
+/// packed from the most significant bit, 16 bit samples are big endian.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ColorType,
+    pub bit_depth: u8,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(
+        width: u32,
+        height: u32,
+        color_type: ColorType,
+        bit_depth: u8,
+        data: Vec<u8>,
+    ) -> Result<Image> {
+        let allowed: &[u8] = match color_type {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            _ => &[8, 16],
+        };
+        if !allowed.contains(&bit_depth) {
+            return Err(Error::Custom("Bit depth is not allowed for the color type"));
+        }
+        let image = Image {
+            width,
+            height,
+            color_type,
+            bit_depth,
+            data,
+        };
+        if image.data.len() != image.row_bytes() * height as usize {
+            return Err(Error::Custom("Image data does not match the image size"));
+        }
+        Ok(image)
+    }
+
+    pub fn channels(&self) -> usize {
+        self.color_type.channels()
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+
+    pub fn row_bytes(&self) -> usize {
+        (self.width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    pub fn row(&self, y: u32) -> &[u8] {
+        let row_bytes = self.row_bytes();
+        &self.data[y as usize * row_bytes..(y as usize + 1) * row_bytes]
+    }
+
+    /// Header describing the image, with compression and filter method 0.
+    pub fn ihdr(&self, interlaced: bool) -> Ihdr {
+        Ihdr {
+            width: self.width,
+            height: self.height,
+            bit_depth: self.bit_depth,
+            color_type: self.color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: interlaced as u8,
+        }
+    }
+
+    /// Filters and compresses the pixels into IDAT data for `ihdr`. Following
+    /// the PNG specification, palette images and images with less than 8 bits
+    /// per sample are not filtered, everything else uses adaptive filtering.
+    pub(crate) fn compress(&self, ihdr: &Ihdr) -> Vec<u8> {
+        let raw = if ihdr.is_interlaced() {
+            interlace(&self.data, ihdr)
+        } else {
+            self.data.clone()
+        };
+        let strategy = if self.color_type == ColorType::Indexed || self.bit_depth < 8 {
+            Strategy::Fixed(FilterType::None)
+        } else {
+            Strategy::Adaptive
+        };
+        zlib::compress(&filter(&raw, ihdr, strategy))
+    }
+
+    /// Encodes the image into a new PNG with nothing but IHDR, IDAT and IEND.
+    /// Indexed images need a palette and go through [`Png::set_image`].
+    pub fn to_png(&self, interlaced: bool) -> Result<Png> {
+        if self.color_type == ColorType::Indexed {
+            return Err(Error::Custom("Indexed image without a PLTE chunk"));
+        }
+        let ihdr = self.ihdr(interlaced);
+        let idat = ChunkType::from_str("IDAT")?;
+        let mut chunks = vec![ihdr.to_chunk()];
+        chunks.extend(
+            self.compress(&ihdr)
+                .chunks(IDAT_SIZE)
+                .map(|data| Chunk::new(idat.clone(), data.to_vec())),
+        );
+        chunks.push(Chunk::new(ChunkType::from_str("IEND")?, Vec::new()));
+        Ok(Png::from_chunks(chunks))
+    }
+
+    /// Expands palette indices to 8 bit RGB, or RGBA when the palette has
+    /// transparency (the data of a `tRNS` chunk).
+    pub fn expand_palette(&self, palette: &[u8], transparency: Option<&[u8]>) -> Result<Image> {
+        if self.color_type != ColorType::Indexed {
+            return Err(Error::Custom("Only indexed images have a palette"));
+        }
+        let (color_type, channels) = match transparency {
+            Some(_) => (ColorType::Rgba, 4),
+            None => (ColorType::Rgb, 3),
+        };
+        let bits = self.bit_depth as usize;
+        let mut data = Vec::with_capacity(self.width as usize * self.height as usize * channels);
+        for y in 0..self.height {
+            let row = self.row(y);
+            for x in 0..self.width as usize {
+                let index = read_bits(row, x * bits, bits) as usize;
+                let color = palette
+                    .get(index * 3..index * 3 + 3)
+                    .ok_or(Error::Custom("Palette index out of range"))?;
+                data.extend_from_slice(color);
+                if let Some(alpha) = transparency {
+                    data.push(alpha.get(index).copied().unwrap_or(255));
+                }
+            }
+        }
+        Image::new(self.width, self.height, color_type, 8, data)
+    }
+}
+
+pub(crate) fn read_bits(row: &[u8], bit: usize, bits: usize) -> u8 {
+    let shift = 8 - bits - bit % 8;
+    (row[bit / 8] >> shift) & (0xff >> (8 - bits))
+}
+
+pub(crate) fn write_bits(row: &mut [u8], bit: usize, bits: usize, value: u8) {
+    let shift = 8 - bits - bit % 8;
+    let mask = (0xff >> (8 - bits)) << shift;
+    row[bit / 8] = (row[bit / 8] & !mask) | (value << shift);
+}
+
+/// Copies pixel `from` of `source` to pixel `to` of `target`.
+fn copy_pixel(source: &[u8], from: usize, target: &mut [u8], to: usize, bits: usize) {
+    if bits >= 8 {
+        let size = bits / 8;
+        target[to * size..(to + 1) * size].copy_from_slice(&source[from * size..(from + 1) * size]);
+    } else {
+        write_bits(
+            target,
+            to * bits,
+            bits,
+            read_bits(source, from * bits, bits),
+        );
+    }
+}
+
+/// Rearranges the unfiltered scanlines of the Adam7 passes into the rows of
+/// the full image.
+pub(crate) fn deinterlace(raw: &[u8], ihdr: &Ihdr) -> Vec<u8> {
+    let bits = ihdr.bits_per_pixel();
+    let row_bytes = ihdr.row_bytes(ihdr.width);
+    let mut data = vec![0; row_bytes * ihdr.height as usize];
+    let mut pos = 0;
+    for pass in ihdr.passes() {
+        let pass_bytes = ihdr.row_bytes(pass.width);
+        for j in 0..pass.height {
+            let line = &raw[pos..pos + pass_bytes];
+            pos += pass_bytes;
+            let y = (pass.y + j * pass.dy) as usize;
+            let row = &mut data[y * row_bytes..(y + 1) * row_bytes];
+            for i in 0..pass.width {
+                let x = pass.x + i * pass.dx;
+                copy_pixel(line, i as usize, row, x as usize, bits);
+            }
+        }
+    }
+    data
+}
+
+/// Splits the rows of an image into the scanlines of the Adam7 passes,
+/// the inverse of [`deinterlace`].
+pub(crate) fn interlace(data: &[u8], ihdr: &Ihdr) -> Vec<u8> {
+    let bits = ihdr.bits_per_pixel();
+    let row_bytes = ihdr.row_bytes(ihdr.width);
+    let mut raw = Vec::with_capacity(data.len() + data.len() / 8);
+    for pass in ihdr.passes() {
+        for j in 0..pass.height {
+            let y = (pass.y + j * pass.dy) as usize;
+            let row = &data[y * row_bytes..(y + 1) * row_bytes];
+            let mut line = vec![0; ihdr.row_bytes(pass.width)];
+            for i in 0..pass.width {
+                let x = pass.x + i * pass.dx;
+                copy_pixel(row, x as usize, &mut line, i as usize, bits);
+            }
+            raw.extend(line);
+        }
+    }
+    raw
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 37 % 256) as u8).collect()
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(Image::new(3, 2, ColorType::Rgb, 8, vec![0; 18]).is_ok());
+        assert!(Image::new(3, 2, ColorType::Rgb, 8, vec![0; 17]).is_err());
+        assert!(Image::new(3, 2, ColorType::Rgb, 4, vec![0; 9]).is_err());
+        let image = Image::new(5, 2, ColorType::Grayscale, 2, vec![0; 4]).unwrap();
+        assert_eq!(image.row_bytes(), 2);
+    }
+
+    #[test]
+    fn test_interlace_roundtrip() {
+        for &(color_type, bit_depth) in &[
+            (ColorType::Grayscale, 1),
+            (ColorType::Indexed, 4),
+            (ColorType::Rgb, 8),
+            (ColorType::Rgba, 16),
+        ] {
+            // 16 pixels wide, so the rows have no padding bits.
+            let len = 16 * color_type.channels() * bit_depth as usize / 8 * 7;
+            let image = Image::new(16, 7, color_type, bit_depth, pattern(len)).unwrap();
+            let ihdr = image.ihdr(true);
+            let raw = interlace(&image.data, &ihdr);
+            let expected: usize = ihdr
+                .passes()
+                .iter()
+                .map(|pass| ihdr.row_bytes(pass.width) * pass.height as usize)
+                .sum();
+            assert_eq!(raw.len(), expected);
+            assert_eq!(deinterlace(&raw, &ihdr), image.data);
+        }
+    }
+
+    #[test]
+    fn test_deinterlace_positions() {
+        // 8 bit grayscale, every pixel holds its own index.
+        let image = Image::new(8, 8, ColorType::Grayscale, 8, (0..64).collect()).unwrap();
+        let ihdr = image.ihdr(true);
+        let raw = interlace(&image.data, &ihdr);
+        assert_eq!(&raw[..4], &[0, 4, 32, 36]);
+        assert_eq!(deinterlace(&raw, &ihdr), image.data);
+    }
+
+    #[test]
+    fn test_expand_palette() {
+        let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+        let image = Image::new(4, 1, ColorType::Indexed, 2, vec![0b0001_1000]).unwrap();
+        let rgb = image.expand_palette(&palette, None).unwrap();
+        assert_eq!(rgb.color_type, ColorType::Rgb);
+        assert_eq!(rgb.data, [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 0, 0]);
+
+        let rgba = image.expand_palette(&palette, Some(&[0])).unwrap();
+        assert_eq!(&rgba.data[..8], &[255, 0, 0, 0, 0, 255, 0, 255]);
+
+        let image = Image::new(1, 1, ColorType::Indexed, 8, vec![3]).unwrap();
+        assert!(image.expand_palette(&palette, None).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use crate::{
+    chunk::Chunk,
+    exif::Exif,
+    ihdr::Ihdr,
+    meta::{Chromaticities, Gamma, Iccp, Metadata, PhysicalDimensions, Srgb, Time},
+    text::TextChunk,
+};
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Formats `data` in the classic `hexdump -C` layout. `start` is the offset
+/// of the first byte inside the chunk data and is only used for the offset
+/// column, so that a dumped range lines up with a full dump.
+pub fn hexdump(data: &[u8], start: usize) -> String {
+    let mut out = String::new();
+    for (i, line) in data.chunks(BYTES_PER_LINE).enumerate() {
+        let _ = write!(out, "{:08x} ", start + i * BYTES_PER_LINE);
+        for j in 0..BYTES_PER_LINE {
+            if j % 8 == 0 {
+                out.push(' ');
+            }
+            match line.get(j) {
+                Some(byte) => {
+                    let _ = write!(out, "{:02x} ", byte);
+                }
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str(" |");
+        out.extend(line.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push_str("|\n");
+    }
+    let _ = writeln!(out, "{:08x}", start + data.len());
+    out
+}
+
+/// Human readable interpretation of the data of chunks pngme knows about.
+pub fn describe(chunk: &Chunk) -> Option<String> {
+    let chunk_type = chunk.chunk_type();
+    match chunk_type.bytes() {
+        b"IHDR" => Ihdr::try_from(chunk).ok().map(|ihdr| ihdr.to_string()),
+        b"IEND" => Some("  End of image\n".to_string()),
+        b"tEXt" | b"zTXt" | b"iTXt" => TextChunk::try_from(chunk)
+            .ok()
+            .map(|text| format!("  {}\n", text)),
+        b"tIME" => meta_line::<Time>(chunk),
+        b"pHYs" => meta_line::<PhysicalDimensions>(chunk),
+        b"gAMA" => meta_line::<Gamma>(chunk),
+        b"cHRM" => meta_line::<Chromaticities>(chunk),
+        b"sRGB" => meta_line::<Srgb>(chunk),
+        b"iCCP" => meta_line::<Iccp>(chunk),
+        b"eXIf" => meta_line::<Exif>(chunk),
+        // Messages hidden by pngme live in private chunks as plain UTF-8.
+        _ if !chunk_type.is_public() => chunk
+            .data_as_string()
+            .ok()
+            .map(|text| format!("  Text: {}\n", text)),
+        _ => None,
+    }
+}
+
+fn meta_line<T: Metadata>(chunk: &Chunk) -> Option<String> {
+    T::from_chunk(chunk)
+        .ok()
+        .map(|value| format!("  {}\n", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_hexdump() {
+        let dump = hexdump(b"IHDR\x00\x01 text with 20 bytes", 0);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            lines[0],
+            "00000000  49 48 44 52 00 01 20 74  65 78 74 20 77 69 74 68  |IHDR.. text with|"
+        );
+        assert_eq!(
+            lines[1],
+            "00000010  20 32 30 20 62 79 74 65  73                       | 20 bytes|"
+        );
+        assert_eq!(lines[2], "00000019");
+    }
+
+    #[test]
+    fn test_hexdump_offset() {
+        let dump = hexdump(b"abc", 0x20);
+        assert!(dump.starts_with("00000020  61 62 63"));
+        assert!(dump.ends_with("00000023\n"));
+    }
+
+    #[test]
+    fn test_describe() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("tEXt").unwrap(),
+            b"Title\0Dice".to_vec(),
+        );
+        assert_eq!(describe(&chunk).unwrap(), "  Title (tEXt): Dice\n");
+
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"hidden".to_vec());
+        assert_eq!(describe(&chunk).unwrap(), "  Text: hidden\n");
+
+        let chunk = Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![1, 2, 3]);
+        assert!(describe(&chunk).is_none());
+    }
+}
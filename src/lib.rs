@@ -1,15 +1,45 @@
 pub mod args;
 mod chunk;
 mod chunk_type;
+mod cipher;
 mod commands;
+pub mod cover;
+pub mod detect;
+pub mod diff;
+pub mod disguise;
+mod ecc;
 mod error;
+pub mod exif;
+pub mod filter;
+pub mod ihdr;
+pub mod image;
+pub mod inspect;
+pub mod lsb;
+pub mod meta;
+pub mod palette;
 pub mod png;
+pub mod policy;
+pub mod stats;
+pub mod text;
+mod zip;
+mod zlib;
 
-pub use args::Commands::{Decode, Encode, Print, Remove};
+#[cfg(feature = "serde")]
+pub use args::Commands::Build;
+pub use args::Commands::{
+    Decode, Detect, Diff, Encode, Exif, Inspect, Meta, Move, Optimize, Polyglot, Print, Remove,
+    Rename, ReplaceData, Strip, Text, Transplant,
+};
 pub use chunk::Chunk;
-pub use chunk_type::ChunkType;
-pub use commands::{decode, encode, print, remove};
+pub use chunk_type::{ChunkType, ChunkTypeBuilder, Properties};
+#[cfg(feature = "serde")]
+pub use commands::build;
+pub use commands::{
+    decode, detect, diff, encode, exif, inspect, meta, move_chunk, optimize, polyglot, print,
+    remove, rename, replace_data, strip, text, transplant,
+};
 pub use png::Png;
+pub use text::{TextChunk, TextKind};
 
 pub const BYTE_SIZE: usize = 4;
 
@@ -0,0 +1,392 @@
+use std::convert::TryInto;
+
+use crate::{ihdr::ColorType, image::Image, Error, Result};
+
+/// Bytes of the length stored in front of the payload.
+const LENGTH_SIZE: usize = 4;
+
+/// Copies of the length. Every bit is decided by majority, so one damaged
+/// copy does not lose a message that error correction could repair.
+const LENGTH_COPIES: usize = 3;
+
+/// Bytes in front of the payload.
+const HEADER_SIZE: usize = LENGTH_SIZE * LENGTH_COPIES;
+
+/// Position of the least significant bit of one sample in [`Image::data`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Slot {
+    byte: usize,
+    shift: u8,
+}
+
+/// Which bits of an image carry the payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// The least significant bit of every color sample.
+    Color,
+    /// Every bit of the color samples of fully transparent pixels, which
+    /// nobody gets to see. With `alpha`, the least significant bit of alpha
+    /// is used too wherever the pixel stays visible.
+    Transparent { alpha: bool },
+}
+
+impl Selection {
+    pub(crate) fn slots(self, image: &Image) -> Result<Vec<Slot>> {
+        match self {
+            Selection::Color => color_slots(image),
+            Selection::Transparent { alpha } => transparent_slots(image, alpha),
+        }
+    }
+}
+
+/// Least significant bits of every color sample in row order, leaving out
+/// alpha. Pixels are visited in image order, so interlaced and progressive
+/// encodings of the same image hide the same bits.
+pub(crate) fn color_slots(image: &Image) -> Result<Vec<Slot>> {
+    if image.color_type == ColorType::Indexed {
+        return Err(Error::Custom(
+            "Changing palette indices changes colors, LSB embedding needs a truecolor or grayscale image",
+        ));
+    }
+    Ok(sample_slots(image))
+}
+
+/// All color bits of pixels with an alpha of 0, followed by the alpha
+/// least significant bits of pixels with an alpha of at least 2. Neither
+/// changes which pixels are transparent, so extraction finds the same bits.
+fn transparent_slots(image: &Image, alpha: bool) -> Result<Vec<Slot>> {
+    if !matches!(
+        image.color_type,
+        ColorType::GrayscaleAlpha | ColorType::Rgba
+    ) {
+        return Err(Error::Custom(
+            "Alpha embedding needs an image with an alpha channel",
+        ));
+    }
+    let sample_bytes = image.bit_depth as usize / 8;
+    let pixel_bytes = image.channels() * sample_bytes;
+    let mut slots = Vec::new();
+    let mut alpha_slots = Vec::new();
+    for (i, pixel) in image.data.chunks_exact(pixel_bytes).enumerate() {
+        let start = i * pixel_bytes;
+        let opacity = pixel[pixel_bytes - sample_bytes..]
+            .iter()
+            .fold(0, |acc, &byte| (acc << 8) | byte as u32);
+        if opacity == 0 {
+            for byte in start..start + pixel_bytes - sample_bytes {
+                slots.extend((0..8).rev().map(|shift| Slot { byte, shift }));
+            }
+        } else if alpha && opacity >= 2 {
+            alpha_slots.push(Slot {
+                byte: start + pixel_bytes - 1,
+                shift: 0,
+            });
+        }
+    }
+    slots.extend(alpha_slots);
+    Ok(slots)
+}
+
+/// Least significant bits of all samples but alpha; for indexed images these
+/// are the parities of the palette indices.
+pub(crate) fn sample_slots(image: &Image) -> Vec<Slot> {
+    let has_alpha = matches!(
+        image.color_type,
+        ColorType::GrayscaleAlpha | ColorType::Rgba
+    );
+    let channels = image.channels();
+    let bits = image.bit_depth as usize;
+    let mut slots = Vec::new();
+    for y in 0..image.height as usize {
+        let row = y * image.row_bytes();
+        for sample in 0..image.width as usize * channels {
+            if has_alpha && sample % channels == channels - 1 {
+                continue;
+            }
+            // Position of the last bit of the sample, counted from the most
+            // significant bit of the row.
+            let bit = (sample + 1) * bits - 1;
+            slots.push(Slot {
+                byte: row + bit / 8,
+                shift: 7 - (bit % 8) as u8,
+            });
+        }
+    }
+    slots
+}
+
+pub(crate) fn read_bit(image: &Image, slot: Slot) -> u8 {
+    (image.data[slot.byte] >> slot.shift) & 1
+}
+
+pub(crate) fn write_bit(image: &mut Image, slot: Slot, bit: u8) {
+    let byte = &mut image.data[slot.byte];
+    *byte = (*byte & !(1 << slot.shift)) | (bit << slot.shift);
+}
+
+/// Packs bits into bytes, most significant bit first. Bits left over after
+/// the last full byte are dropped.
+fn pack(bits: impl Iterator<Item = u8>) -> Vec<u8> {
+    let bits: Vec<u8> = bits.collect();
+    bits.chunks_exact(8)
+        .map(|byte| byte.iter().fold(0, |acc, bit| (acc << 1) | bit))
+        .collect()
+}
+
+fn unpack(data: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    data.iter()
+        .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1))
+}
+
+/// Largest Hamming code parameter matrix embedding accepts.
+pub const MAX_MATRIX: u32 = 8;
+
+/// Matrix embedding with parameter `k` hides `k` bits in every group of
+/// `2^k - 1` slots by flipping at most one of them, using the syndrome of a
+/// Hamming code. 1 is plain LSB embedding, higher values change fewer bits
+/// per payload bit but hold less.
+fn check_matrix(matrix: u32) -> Result<()> {
+    if (1..=MAX_MATRIX).contains(&matrix) {
+        Ok(())
+    } else {
+        Err(Error::Custom(
+            "Matrix embedding takes 1 to 8 bits per group",
+        ))
+    }
+}
+
+fn group_size(matrix: u32) -> usize {
+    (1 << matrix) - 1
+}
+
+/// Positions, counted from 1, of the set bits in `group` xored together.
+fn syndrome(image: &Image, group: &[Slot]) -> usize {
+    group
+        .iter()
+        .enumerate()
+        .filter(|&(_, &slot)| read_bit(image, slot) == 1)
+        .fold(0, |acc, (i, _)| acc ^ (i + 1))
+}
+
+/// Payload bytes that fit into `slots` next to the length.
+pub(crate) fn slots_capacity(slots: &[Slot], matrix: u32) -> usize {
+    (slots.len() / group_size(matrix) * matrix as usize / 8).saturating_sub(HEADER_SIZE)
+}
+
+/// Payload bytes `image` can hide in the selected bits.
+pub fn capacity(image: &Image, selection: Selection, matrix: u32) -> Result<usize> {
+    check_matrix(matrix)?;
+    Ok(slots_capacity(&selection.slots(image)?, matrix))
+}
+
+/// Hides `payload` in the selected bits, preceded by its length, and returns
+/// how many bits changed.
+pub fn embed(
+    image: &mut Image,
+    payload: &[u8],
+    selection: Selection,
+    matrix: u32,
+) -> Result<usize> {
+    let slots = selection.slots(image)?;
+    embed_in(image, &slots, payload, matrix)
+}
+
+pub(crate) fn embed_in(
+    image: &mut Image,
+    slots: &[Slot],
+    payload: &[u8],
+    matrix: u32,
+) -> Result<usize> {
+    check_matrix(matrix)?;
+    let length = (payload.len() as u32).to_be_bytes();
+    let mut framed = length.repeat(LENGTH_COPIES);
+    framed.extend_from_slice(payload);
+    let (n, k) = (group_size(matrix), matrix as usize);
+    if (framed.len() * 8).div_ceil(k) > slots.len() / n {
+        return Err(Error::Custom("The message does not fit into the image"));
+    }
+    let bits: Vec<u8> = unpack(&framed).collect();
+    let mut changed = 0;
+    for (group, message) in slots.chunks_exact(n).zip(bits.chunks(k)) {
+        // The last group may carry fewer bits, padded with zeros.
+        let wanted = message
+            .iter()
+            .fold(0, |acc, &bit| (acc << 1) | bit as usize)
+            << (k - message.len());
+        let flip = syndrome(image, group) ^ wanted;
+        if flip != 0 {
+            let slot = group[flip - 1];
+            write_bit(image, slot, read_bit(image, slot) ^ 1);
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+/// Reads a payload hidden by [`embed`] with the same selection and matrix.
+pub fn extract(image: &Image, selection: Selection, matrix: u32) -> Result<Vec<u8>> {
+    extract_from(image, &selection.slots(image)?, matrix)
+}
+
+pub(crate) fn extract_from(image: &Image, slots: &[Slot], matrix: u32) -> Result<Vec<u8>> {
+    check_matrix(matrix)?;
+    let (n, k) = (group_size(matrix), matrix as usize);
+    let read = |bytes: usize| {
+        let mut bits: Vec<u8> = slots
+            .chunks_exact(n)
+            .take((bytes * 8).div_ceil(k))
+            .flat_map(|group| {
+                let syndrome = syndrome(image, group);
+                (0..k).rev().map(move |i| ((syndrome >> i) & 1) as u8)
+            })
+            .collect();
+        bits.truncate(bytes * 8);
+        pack(bits.into_iter())
+    };
+    if slots.len() / n * k < HEADER_SIZE * 8 {
+        return Err(Error::Custom("The image is too small to hide a message"));
+    }
+    let header = read(HEADER_SIZE);
+    let copies: Vec<&[u8]> = header.chunks(LENGTH_SIZE).collect();
+    let length: Vec<u8> = (0..LENGTH_SIZE)
+        .map(|i| {
+            let (a, b, c) = (copies[0][i], copies[1][i], copies[2][i]);
+            (a & b) | (a & c) | (b & c)
+        })
+        .collect();
+    let length = u32::from_be_bytes(length[..].try_into()?) as usize;
+    if length > slots_capacity(slots, matrix) {
+        return Err(Error::Custom("No message is hidden in the image"));
+    }
+    Ok(read(HEADER_SIZE + length).split_off(HEADER_SIZE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(color_type: ColorType, bit_depth: u8) -> Image {
+        let probe = Image {
+            width: 17,
+            height: 9,
+            color_type,
+            bit_depth,
+            data: Vec::new(),
+        };
+        let len = probe.row_bytes() * 9;
+        let data = (0..len).map(|i| (i * 91 % 256) as u8).collect();
+        Image::new(17, 9, color_type, bit_depth, data).unwrap()
+    }
+
+    #[test]
+    fn test_slots() {
+        let slots = color_slots(&image(ColorType::Rgba, 8)).unwrap();
+        assert_eq!(slots.len(), 17 * 9 * 3);
+        assert_eq!(slots[3], Slot { byte: 4, shift: 0 });
+
+        let slots = color_slots(&image(ColorType::Rgb, 16)).unwrap();
+        assert_eq!(slots[0], Slot { byte: 1, shift: 0 });
+
+        let slots = color_slots(&image(ColorType::Grayscale, 2)).unwrap();
+        assert_eq!(slots[0], Slot { byte: 0, shift: 6 });
+        assert_eq!(slots[5], Slot { byte: 1, shift: 4 });
+        // Rows of 17 two bit pixels take 5 bytes.
+        assert_eq!(slots[17], Slot { byte: 5, shift: 6 });
+
+        assert!(color_slots(&image(ColorType::Indexed, 8)).is_err());
+    }
+
+    #[test]
+    fn test_embed_extract() {
+        for &(color_type, bit_depth) in &[
+            (ColorType::Rgb, 8),
+            (ColorType::Rgba, 16),
+            (ColorType::Grayscale, 4),
+        ] {
+            let mut image = image(color_type, bit_depth);
+            let original = image.clone();
+            embed(&mut image, b"hi", Selection::Color, 1).unwrap();
+            assert_eq!(extract(&image, Selection::Color, 1).unwrap(), b"hi");
+            // Only least significant bits change.
+            for (a, b) in image.data.iter().zip(&original.data) {
+                assert_eq!(a & 0b1110_1110, b & 0b1110_1110);
+            }
+        }
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut image = image(ColorType::Rgb, 8);
+        let capacity = capacity(&image, Selection::Color, 1).unwrap();
+        assert_eq!(capacity, 17 * 9 * 3 / 8 - HEADER_SIZE);
+        assert!(embed(&mut image, &vec![0; capacity], Selection::Color, 1).is_ok());
+        assert!(embed(&mut image, &vec![0; capacity + 1], Selection::Color, 1).is_err());
+    }
+
+    #[test]
+    fn test_transparent() {
+        // Every third pixel is transparent, alpha 1 must stay as it is.
+        let mut image = image(ColorType::Rgba, 8);
+        for (i, pixel) in image.data.chunks_mut(4).enumerate() {
+            pixel[3] = [0, 1, 200][i % 3];
+        }
+        let transparent = (17 * 9usize).div_ceil(3);
+        let selection = Selection::Transparent { alpha: false };
+        assert_eq!(
+            capacity(&image, selection, 1).unwrap(),
+            transparent * 3 - HEADER_SIZE
+        );
+        let with_alpha = Selection::Transparent { alpha: true };
+        let alpha_lsbs = 17 * 9 / 3;
+        assert_eq!(
+            capacity(&image, with_alpha, 1).unwrap(),
+            (transparent * 24 + alpha_lsbs) / 8 - HEADER_SIZE
+        );
+
+        let original = image.clone();
+        let payload = vec![0xa5; capacity(&image, with_alpha, 1).unwrap()];
+        embed(&mut image, &payload, with_alpha, 1).unwrap();
+        assert_eq!(extract(&image, with_alpha, 1).unwrap(), payload);
+        for (a, b) in image.data.chunks(4).zip(original.data.chunks(4)) {
+            match b[3] {
+                0 => assert_eq!(a[3], 0),
+                1 => assert_eq!(a, b),
+                _ => assert_eq!((a[..3].to_vec(), a[3] | 1), (b[..3].to_vec(), b[3] | 1)),
+            }
+        }
+
+        assert!(capacity(&image, selection, 1).is_ok());
+        assert!(capacity(&self::image(ColorType::Rgb, 8), selection, 1).is_err());
+    }
+
+    #[test]
+    fn test_matrix() {
+        let original = image(ColorType::Rgb, 8);
+        let payload: Vec<u8> = (0..3u8).map(|i| i.wrapping_mul(37)).collect();
+        let mut changes = Vec::new();
+        for matrix in 1..=4 {
+            let mut image = original.clone();
+            let changed = embed(&mut image, &payload, Selection::Color, matrix).unwrap();
+            assert_eq!(extract(&image, Selection::Color, matrix).unwrap(), payload);
+            let differing = image
+                .data
+                .iter()
+                .zip(&original.data)
+                .filter(|(a, b)| a != b)
+                .count();
+            assert_eq!(changed, differing);
+            // At most one change per group.
+            assert!(changed <= ((payload.len() + HEADER_SIZE) * 8).div_ceil(matrix as usize));
+            changes.push(changed);
+        }
+        assert!(changes.windows(2).all(|pair| pair[1] < pair[0]));
+
+        let slots = 17 * 9 * 3;
+        assert_eq!(
+            capacity(&original, Selection::Color, 3).unwrap(),
+            slots / 7 * 3 / 8 - HEADER_SIZE
+        );
+        assert!(capacity(&original, Selection::Color, 0).is_err());
+        assert!(capacity(&original, Selection::Color, 9).is_err());
+    }
+}
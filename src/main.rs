@@ -1,4 +1,11 @@
-use pngme::{args::Opt, decode, encode, print, remove, Decode, Encode, Print, Remove, Result};
+use pngme::{
+    args::Opt, decode, detect, diff, encode, exif, inspect, meta, move_chunk, optimize, polyglot,
+    print, remove, rename, replace_data, strip, text, transplant, Decode, Detect, Diff, Encode,
+    Exif, Inspect, Meta, Move, Optimize, Polyglot, Print, Remove, Rename, ReplaceData, Result,
+    Strip, Text, Transplant,
+};
+#[cfg(feature = "serde")]
+use pngme::{build, Build};
 use structopt::StructOpt;
 
 fn main() {
@@ -31,8 +38,65 @@ fn run() -> Result<()> {
         } => remove(input, args)?,
         Opt {
             input,
-            commands: Print(_),
-        } => print(&input)?,
+            commands: Print(args),
+        } => print(input, args)?,
+        #[cfg(feature = "serde")]
+        Opt {
+            input,
+            commands: Build(args),
+        } => build(input, args)?,
+        Opt {
+            input,
+            commands: Inspect(args),
+        } => inspect(input, args)?,
+        Opt {
+            input,
+            commands: Text(args),
+        } => text(input, args)?,
+        Opt {
+            input,
+            commands: Meta(args),
+        } => meta(input, args)?,
+        Opt {
+            input,
+            commands: Exif(args),
+        } => exif(input, args)?,
+        Opt {
+            input,
+            commands: Strip(args),
+        } => strip(input, args)?,
+        Opt {
+            input,
+            commands: Polyglot(args),
+        } => polyglot(input, args)?,
+        Opt {
+            input,
+            commands: Optimize(args),
+        } => optimize(input, args)?,
+        Opt {
+            input,
+            commands: Detect(args),
+        } => detect(input, args)?,
+        Opt {
+            input,
+            commands: Diff(args),
+        } => diff(input, args)?,
+        Opt {
+            input,
+            commands: Transplant(args),
+        } => transplant(input, args)?,
+        Opt {
+            input,
+            commands: Move(args),
+        } => move_chunk(input, args)?,
+        Opt {
+            input,
+            commands: Rename(args),
+        } => rename(input, args)?,
+        Opt {
+            input,
+            commands: ReplaceData(args),
+        } => replace_data(input, args)?,
     }
     Ok(())
 }
@@ -0,0 +1,523 @@
+use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    text::{from_latin1, split_nul, to_latin1},
+    zlib, Error, Result,
+};
+
+/// Typed view of an ancillary chunk that holds image metadata.
+pub trait Metadata: Sized + fmt::Display {
+    /// Chunk type the value is stored in.
+    const CHUNK_TYPE: &'static str;
+
+    fn from_data(data: &[u8]) -> Result<Self>;
+
+    fn to_data(&self) -> Result<Vec<u8>>;
+
+    fn from_chunk(chunk: &Chunk) -> Result<Self> {
+        if chunk.chunk_type().to_string() != Self::CHUNK_TYPE {
+            return Err(Error::Custom("Unexpected chunk type"));
+        }
+        Self::from_data(chunk.data())
+    }
+
+    fn to_chunk(&self) -> Result<Chunk> {
+        Ok(Chunk::new(
+            ChunkType::from_str(Self::CHUNK_TYPE)?,
+            self.to_data()?,
+        ))
+    }
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .ok_or(Error::Custom("Chunk data is too short"))?;
+    Ok(u32::from_be_bytes(bytes.try_into()?))
+}
+
+fn expect_length(data: &[u8], length: usize) -> Result<()> {
+    if data.len() != length {
+        return Err(Error::Custom("Chunk data has an invalid length"));
+    }
+    Ok(())
+}
+
+/// Several PNG chunks store real numbers multiplied by 100000, as integers
+/// of at most 2^31 - 1.
+fn to_fixed(value: f64) -> Result<u32> {
+    let fixed = (value * 100_000.0).round();
+    if !(0.0..=i32::MAX as f64).contains(&fixed) {
+        return Err(Error::Custom(
+            "Values must be finite, not negative and at most 21474.83647",
+        ));
+    }
+    Ok(fixed as u32)
+}
+
+fn from_fixed(value: u32) -> f64 {
+    value as f64 / 100_000.0
+}
+
+/// Last modification time, `tIME`. Always UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Time {
+    pub fn now() -> Time {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let (days, rem) = (secs / 86_400, secs % 86_400);
+
+        // Civil date from days since the epoch, after Howard Hinnant.
+        let z = days as i64 + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+
+        Time {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (rem / 3600) as u8,
+            minute: (rem / 60 % 60) as u8,
+            second: (rem % 60) as u8,
+        }
+    }
+}
+
+impl Metadata for Time {
+    const CHUNK_TYPE: &'static str = "tIME";
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        expect_length(data, 7)?;
+        Ok(Time {
+            year: u16::from_be_bytes(data[0..2].try_into()?),
+            month: data[2],
+            day: data[3],
+            hour: data[4],
+            minute: data[5],
+            second: data[6],
+        })
+    }
+
+    fn to_data(&self) -> Result<Vec<u8>> {
+        let mut data = self.year.to_be_bytes().to_vec();
+        data.extend(&[self.month, self.day, self.hour, self.minute, self.second]);
+        Ok(data)
+    }
+}
+
+impl FromStr for Time {
+    type Err = Error<'static>;
+
+    /// Parses `now` or `YYYY-MM-DDTHH:MM:SS`.
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "now" {
+            return Ok(Time::now());
+        }
+        let fields: Vec<&str> = s.split(&['-', 'T', ':'][..]).collect();
+        if fields.len() != 6 {
+            return Err(Error::Custom("Expected time as YYYY-MM-DDTHH:MM:SS"));
+        }
+        let time = Time {
+            year: fields[0].parse()?,
+            month: fields[1].parse()?,
+            day: fields[2].parse()?,
+            hour: fields[3].parse()?,
+            minute: fields[4].parse()?,
+            second: fields[5].parse()?,
+        };
+        if !(1..=12).contains(&time.month)
+            || !(1..=31).contains(&time.day)
+            || time.hour > 23
+            || time.minute > 59
+            || time.second > 60
+        {
+            return Err(Error::Custom("Time is out of range"));
+        }
+        Ok(time)
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Unknown,
+    Meter,
+}
+
+/// Physical pixel dimensions, `pHYs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicalDimensions {
+    pub pixels_per_unit_x: u32,
+    pub pixels_per_unit_y: u32,
+    pub unit: Unit,
+}
+
+impl PhysicalDimensions {
+    const METERS_PER_INCH: f64 = 0.0254;
+
+    pub fn from_dpi(dpi: f64) -> Result<PhysicalDimensions> {
+        let ppm = (dpi / Self::METERS_PER_INCH).round();
+        if !(1.0..=i32::MAX as f64).contains(&ppm) {
+            return Err(Error::Custom("The resolution must be a positive number"));
+        }
+        Ok(PhysicalDimensions {
+            pixels_per_unit_x: ppm as u32,
+            pixels_per_unit_y: ppm as u32,
+            unit: Unit::Meter,
+        })
+    }
+
+    /// Horizontal and vertical resolution in dots per inch, if the unit is known.
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        match self.unit {
+            Unit::Meter => Some((
+                self.pixels_per_unit_x as f64 * Self::METERS_PER_INCH,
+                self.pixels_per_unit_y as f64 * Self::METERS_PER_INCH,
+            )),
+            Unit::Unknown => None,
+        }
+    }
+}
+
+impl Metadata for PhysicalDimensions {
+    const CHUNK_TYPE: &'static str = "pHYs";
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        expect_length(data, 9)?;
+        let unit = match data[8] {
+            0 => Unit::Unknown,
+            1 => Unit::Meter,
+            _ => return Err(Error::Custom("Unknown pHYs unit")),
+        };
+        Ok(PhysicalDimensions {
+            pixels_per_unit_x: u32_at(data, 0)?,
+            pixels_per_unit_y: u32_at(data, 4)?,
+            unit,
+        })
+    }
+
+    fn to_data(&self) -> Result<Vec<u8>> {
+        let mut data = self.pixels_per_unit_x.to_be_bytes().to_vec();
+        data.extend(&self.pixels_per_unit_y.to_be_bytes());
+        data.push(match self.unit {
+            Unit::Unknown => 0,
+            Unit::Meter => 1,
+        });
+        Ok(data)
+    }
+}
+
+impl fmt::Display for PhysicalDimensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.dpi() {
+            Some((x, y)) => write!(
+                f,
+                "{} x {} pixels per meter ({:.0} x {:.0} dpi)",
+                self.pixels_per_unit_x, self.pixels_per_unit_y, x, y
+            ),
+            None => write!(
+                f,
+                "{} x {} (aspect ratio only)",
+                self.pixels_per_unit_x, self.pixels_per_unit_y
+            ),
+        }
+    }
+}
+
+/// Image gamma, `gAMA`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gamma(pub f64);
+
+impl Metadata for Gamma {
+    const CHUNK_TYPE: &'static str = "gAMA";
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        expect_length(data, 4)?;
+        Ok(Gamma(from_fixed(u32_at(data, 0)?)))
+    }
+
+    fn to_data(&self) -> Result<Vec<u8>> {
+        let gamma = to_fixed(self.0)?;
+        if gamma == 0 {
+            return Err(Error::Custom("Gamma must be a positive number"));
+        }
+        Ok(gamma.to_be_bytes().to_vec())
+    }
+}
+
+impl fmt::Display for Gamma {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.5}", self.0)
+    }
+}
+
+/// Primary chromaticities and white point, `cHRM`, as CIE x and y pairs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chromaticities {
+    pub white: (f64, f64),
+    pub red: (f64, f64),
+    pub green: (f64, f64),
+    pub blue: (f64, f64),
+}
+
+impl Metadata for Chromaticities {
+    const CHUNK_TYPE: &'static str = "cHRM";
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        expect_length(data, 32)?;
+        let pair = |i: usize| -> Result<(f64, f64)> {
+            Ok((
+                from_fixed(u32_at(data, i * 8)?),
+                from_fixed(u32_at(data, i * 8 + 4)?),
+            ))
+        };
+        Ok(Chromaticities {
+            white: pair(0)?,
+            red: pair(1)?,
+            green: pair(2)?,
+            blue: pair(3)?,
+        })
+    }
+
+    fn to_data(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(32);
+        for &(x, y) in &[self.white, self.red, self.green, self.blue] {
+            data.extend_from_slice(&to_fixed(x)?.to_be_bytes());
+            data.extend_from_slice(&to_fixed(y)?.to_be_bytes());
+        }
+        Ok(data)
+    }
+}
+
+impl FromStr for Chromaticities {
+    type Err = Error<'static>;
+
+    /// Parses eight comma separated values: white, red, green and blue x,y.
+    fn from_str(s: &str) -> Result<Self> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| Error::Custom("Chromaticities must be numbers"))?;
+        if values.len() != 8 {
+            return Err(Error::Custom("Expected eight chromaticity values"));
+        }
+        Ok(Chromaticities {
+            white: (values[0], values[1]),
+            red: (values[2], values[3]),
+            green: (values[4], values[5]),
+            blue: (values[6], values[7]),
+        })
+    }
+}
+
+impl fmt::Display for Chromaticities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "white ({:.5}, {:.5}), red ({:.5}, {:.5}), green ({:.5}, {:.5}), blue ({:.5}, {:.5})",
+            self.white.0,
+            self.white.1,
+            self.red.0,
+            self.red.1,
+            self.green.0,
+            self.green.1,
+            self.blue.0,
+            self.blue.1
+        )
+    }
+}
+
+/// Rendering intent of an `sRGB` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Srgb {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+impl Metadata for Srgb {
+    const CHUNK_TYPE: &'static str = "sRGB";
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        expect_length(data, 1)?;
+        match data[0] {
+            0 => Ok(Srgb::Perceptual),
+            1 => Ok(Srgb::RelativeColorimetric),
+            2 => Ok(Srgb::Saturation),
+            3 => Ok(Srgb::AbsoluteColorimetric),
+            _ => Err(Error::Custom("Unknown rendering intent")),
+        }
+    }
+
+    fn to_data(&self) -> Result<Vec<u8>> {
+        Ok(vec![*self as u8])
+    }
+}
+
+impl FromStr for Srgb {
+    type Err = Error<'static>;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "perceptual" => Ok(Srgb::Perceptual),
+            "relative" => Ok(Srgb::RelativeColorimetric),
+            "saturation" => Ok(Srgb::Saturation),
+            "absolute" => Ok(Srgb::AbsoluteColorimetric),
+            _ => Err(Error::Custom(
+                "Expected perceptual, relative, saturation or absolute",
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Srgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Srgb::Perceptual => "perceptual",
+            Srgb::RelativeColorimetric => "relative colorimetric",
+            Srgb::Saturation => "saturation",
+            Srgb::AbsoluteColorimetric => "absolute colorimetric",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Embedded ICC colour profile, `iCCP`. The profile is kept uncompressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Iccp {
+    pub name: String,
+    pub profile: Vec<u8>,
+}
+
+impl Metadata for Iccp {
+    const CHUNK_TYPE: &'static str = "iCCP";
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        let (name, rest) = split_nul(data)?;
+        match rest.split_first() {
+            Some((0, profile)) => Ok(Iccp {
+                name: from_latin1(name),
+                profile: zlib::decompress(profile)?,
+            }),
+            Some(_) => Err(Error::Custom("Unknown compression method")),
+            None => Err(Error::Custom("iCCP chunk is truncated")),
+        }
+    }
+
+    fn to_data(&self) -> Result<Vec<u8>> {
+        let mut data = to_latin1(&self.name)?;
+        if data.is_empty() || data.len() > 79 || data.contains(&0) {
+            return Err(Error::Custom("Profile name must be 1-79 characters long"));
+        }
+        data.extend(&[0, 0]);
+        data.extend(zlib::compress(&self.profile));
+        Ok(data)
+    }
+}
+
+impl fmt::Display for Iccp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}', {} bytes", self.name, self.profile.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip<T: Metadata>(value: &T) -> T {
+        T::from_chunk(&value.to_chunk().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_time() {
+        let time = Time::from_str("2019-08-22T11:30:21").unwrap();
+        assert_eq!(time.to_data().unwrap(), vec![7, 227, 8, 22, 11, 30, 21]);
+        assert_eq!(roundtrip(&time), time);
+        assert_eq!(time.to_string(), "2019-08-22T11:30:21Z");
+        assert!(Time::from_str("2019-13-22T11:30:21").is_err());
+        assert!(Time::now().year >= 2020);
+    }
+
+    #[test]
+    fn test_physical_dimensions() {
+        let phys = PhysicalDimensions::from_dpi(72.0).unwrap();
+        assert_eq!(phys.pixels_per_unit_x, 2835);
+        assert_eq!(roundtrip(&phys), phys);
+        let (x, _) = phys.dpi().unwrap();
+        assert!((x - 72.0).abs() < 0.01);
+        assert!(PhysicalDimensions::from_data(&[0; 8]).is_err());
+        for &dpi in &[0.0, -300.0, f64::NAN, f64::INFINITY] {
+            assert!(PhysicalDimensions::from_dpi(dpi).is_err(), "{}", dpi);
+        }
+    }
+
+    #[test]
+    fn test_gamma() {
+        let gamma = Gamma::from_data(&[0, 0, 177, 143]).unwrap();
+        assert!((gamma.0 - 0.45455).abs() < 1e-9);
+        assert_eq!(gamma.to_data().unwrap(), vec![0, 0, 177, 143]);
+        for &value in &[0.0, -1.0, f64::NAN, f64::INFINITY, 1e9] {
+            assert!(Gamma(value).to_data().is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_chromaticities() {
+        let chrm = Chromaticities::from_str("0.3127,0.329,0.64,0.33,0.3,0.6,0.15,0.06").unwrap();
+        assert_eq!(roundtrip(&chrm), chrm);
+        assert!(Chromaticities::from_str("0.3127,0.329").is_err());
+        let chrm = Chromaticities::from_str("0.3127,-0.329,0.64,0.33,0.3,0.6,0.15,0.06").unwrap();
+        assert!(chrm.to_data().is_err());
+    }
+
+    #[test]
+    fn test_srgb() {
+        assert_eq!(Srgb::from_data(&[2]).unwrap(), Srgb::Saturation);
+        assert_eq!(roundtrip(&Srgb::Perceptual), Srgb::Perceptual);
+        assert!(Srgb::from_data(&[4]).is_err());
+    }
+
+    #[test]
+    fn test_iccp() {
+        let iccp = Iccp {
+            name: "sRGB IEC61966-2.1".to_string(),
+            profile: vec![42; 128],
+        };
+        assert_eq!(roundtrip(&iccp), iccp);
+        assert!(Gamma::from_chunk(&iccp.to_chunk().unwrap()).is_err());
+    }
+}
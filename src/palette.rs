@@ -0,0 +1,300 @@
+use crate::{
+    ihdr::ColorType,
+    image::{read_bits, write_bits, Image},
+    lsb,
+    png::Png,
+    Error, Result,
+};
+
+/// Entries a palette holds at most.
+const MAX_ENTRIES: usize = 256;
+
+/// How [`embed`] arranged the palette so the parity of an index can change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Every color is followed by a copy of itself, as left by an earlier
+    /// embedding. Flipping the parity keeps the colors.
+    Paired,
+    /// Every color was duplicated into a pair, the image looks the same.
+    Duplicated,
+    /// The palette was too full to duplicate every color. The colors that
+    /// did not fit twice share a pair with their nearest color, flipping the
+    /// parity of those moves to it.
+    Nearest,
+}
+
+/// Palette entry with its alpha from tRNS, 255 when there is none.
+type Entry = [u8; 4];
+
+fn entries(png: &Png) -> Result<Vec<Entry>> {
+    let palette = png
+        .chunk_by_type("PLTE")
+        .ok_or(Error::Custom("Indexed image without a PLTE chunk"))?
+        .data();
+    let alpha = png.chunk_by_type("tRNS").map(|chunk| chunk.data());
+    Ok(palette
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(i, rgb)| {
+            let a = alpha.and_then(|alpha| alpha.get(i)).copied().unwrap_or(255);
+            [rgb[0], rgb[1], rgb[2], a]
+        })
+        .collect())
+}
+
+fn is_paired(entries: &[Entry]) -> bool {
+    entries.len() % 2 == 0 && entries.chunks_exact(2).all(|pair| pair[0] == pair[1])
+}
+
+/// Squared distance of two entries, alpha counting like a color channel.
+fn distance(a: &Entry, b: &Entry) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(&a, &b)| (a as i32 - b as i32).pow(2) as u32)
+        .sum()
+}
+
+/// New palette and the new index of every old entry. Whatever the layout,
+/// the entry at `i ^ 1` exists and is the same color as the one at `i`,
+/// except for [`Layout::Nearest`] where it is the closest color left.
+fn arrange(entries: &[Entry]) -> (Layout, Vec<Entry>, Vec<u8>) {
+    if is_paired(entries) {
+        let remap = (0..entries.len()).map(|i| i as u8).collect();
+        (Layout::Paired, entries.to_vec(), remap)
+    } else if entries.len() * 2 <= MAX_ENTRIES {
+        let arranged = entries.iter().flat_map(|&entry| [entry, entry]).collect();
+        let remap = (0..entries.len()).map(|i| (i * 2) as u8).collect();
+        (Layout::Duplicated, arranged, remap)
+    } else {
+        // Only MAX_ENTRIES - len colors fit twice, the others are paired
+        // with each other, closest pairs first.
+        let mixed = entries.len() - MAX_ENTRIES / 2;
+        let mut candidates: Vec<(u32, usize, usize)> = (0..entries.len())
+            .flat_map(|i| (i + 1..entries.len()).map(move |j| (i, j)))
+            .map(|(i, j)| (distance(&entries[i], &entries[j]), i, j))
+            .collect();
+        candidates.sort_unstable();
+        let mut paired = vec![false; entries.len()];
+        let mut pairs = Vec::with_capacity(MAX_ENTRIES / 2);
+        for (_, i, j) in candidates {
+            if pairs.len() == mixed {
+                break;
+            }
+            if !paired[i] && !paired[j] {
+                paired[i] = true;
+                paired[j] = true;
+                pairs.push((i, j));
+            }
+        }
+        pairs.extend((0..entries.len()).filter(|&i| !paired[i]).map(|i| (i, i)));
+        pairs.sort_unstable();
+
+        let mut arranged = Vec::with_capacity(MAX_ENTRIES);
+        let mut remap = vec![0; entries.len()];
+        for (k, &(i, j)) in pairs.iter().enumerate() {
+            arranged.push(entries[i]);
+            arranged.push(entries[j]);
+            remap[i] = (2 * k) as u8;
+            if j != i {
+                remap[j] = (2 * k + 1) as u8;
+            }
+        }
+        (Layout::Nearest, arranged, remap)
+    }
+}
+
+fn indexed_image(png: &Png) -> Result<Image> {
+    let image = png.stored_image()?;
+    if image.color_type != ColorType::Indexed {
+        return Err(Error::Custom(
+            "Palette embedding needs an indexed image, try lsb",
+        ));
+    }
+    Ok(image)
+}
+
+/// Hides `payload` in the parity of the palette indices, preceded by its
+/// length, and rewrites the palette so every index has a twin of the other
+/// parity. `matrix` works as in [`lsb::embed`]. Returns how the palette was
+/// arranged.
+pub fn embed(png: &mut Png, payload: &[u8], interlaced: bool, matrix: u32) -> Result<Layout> {
+    let image = indexed_image(png)?;
+    let entries = entries(png)?;
+    let (layout, arranged, remap) = arrange(&entries);
+
+    let bit_depth = if arranged.len() <= 1 << image.bit_depth {
+        image.bit_depth
+    } else {
+        8
+    };
+    let mut target = Image {
+        bit_depth,
+        data: Vec::new(),
+        ..image.clone()
+    };
+    let (old_bits, new_bits) = (image.bit_depth as usize, bit_depth as usize);
+    target.data = vec![0; target.row_bytes() * image.height as usize];
+    let row_bytes = target.row_bytes();
+    for y in 0..image.height {
+        let row = image.row(y);
+        let out = &mut target.data[y as usize * row_bytes..(y as usize + 1) * row_bytes];
+        for x in 0..image.width as usize {
+            let index = read_bits(row, x * old_bits, old_bits) as usize;
+            let index = *remap
+                .get(index)
+                .ok_or(Error::Custom("Palette index out of range"))?;
+            write_bits(out, x * new_bits, new_bits, index);
+        }
+    }
+    let slots = lsb::sample_slots(&target);
+    lsb::embed_in(&mut target, &slots, payload, matrix)?;
+
+    let palette = arranged.iter().flat_map(|entry| &entry[..3]).copied();
+    let mut alpha: Vec<u8> = arranged.iter().map(|entry| entry[3]).collect();
+    while alpha.last() == Some(&255) {
+        alpha.pop();
+    }
+    let transparency = Some(alpha).filter(|alpha| !alpha.is_empty());
+    png.set_palette(palette.collect(), transparency, &remap)?;
+    png.set_image(&target, interlaced)?;
+    Ok(layout)
+}
+
+/// Reads a payload hidden by [`embed`].
+pub fn extract(png: &Png, matrix: u32) -> Result<Vec<u8>> {
+    let image = indexed_image(png)?;
+    lsb::extract_from(&image, &lsb::sample_slots(&image), matrix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType};
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    /// 24x16 image cycling through `colors` gray levels.
+    fn indexed_png(colors: usize, bit_depth: u8) -> Png {
+        let mut image = Image::new(24, 16, ColorType::Indexed, 8, vec![0; 24 * 16]).unwrap();
+        for (i, index) in image.data.iter_mut().enumerate() {
+            *index = (i * 7 % colors) as u8;
+        }
+        let palette = (0..colors).flat_map(|i| vec![(255 - i) as u8; 3]).collect();
+        let mut png = Png::from_chunks(vec![
+            image.ihdr(false).to_chunk(),
+            chunk("PLTE", palette),
+            chunk("tRNS", vec![0, 128]),
+            chunk("bKGD", vec![1]),
+            chunk("IDAT", Vec::new()),
+            chunk("IEND", Vec::new()),
+        ]);
+        png.set_image(&image, false).unwrap();
+        if bit_depth < 8 {
+            // Pack the indices by going through a palette that fits.
+            let packed = Image {
+                bit_depth,
+                data: Vec::new(),
+                ..image.clone()
+            };
+            let bits = bit_depth as usize;
+            let mut data = vec![0; packed.row_bytes() * 16];
+            for y in 0..16 {
+                for x in 0..24 {
+                    let row = &mut data[y * packed.row_bytes()..(y + 1) * packed.row_bytes()];
+                    write_bits(row, x * bits, bits, image.data[y * 24 + x]);
+                }
+            }
+            let packed = Image { data, ..packed };
+            png.set_image(&packed, false).unwrap();
+        }
+        png
+    }
+
+    #[test]
+    fn test_duplicated() {
+        let mut png = indexed_png(5, 4);
+        let pixels = png.image().unwrap();
+        let layout = embed(&mut png, b"Message", false, 1).unwrap();
+        assert_eq!(layout, Layout::Duplicated);
+        assert_eq!(extract(&png, 1).unwrap(), b"Message");
+        // Duplicating keeps every color and fits the 4 bit indices.
+        assert_eq!(png.image().unwrap(), pixels);
+        assert_eq!(png.ihdr().unwrap().bit_depth, 4);
+        assert_eq!(png.chunk_by_type("PLTE").unwrap().data().len(), 10 * 3);
+        assert_eq!(png.chunk_by_type("tRNS").unwrap().data(), &[0, 0, 128, 128]);
+        assert_eq!(png.chunk_by_type("bKGD").unwrap().data(), &[2]);
+
+        // Embedding again reuses the pairs.
+        let layout = embed(&mut png, b"Other", true, 1).unwrap();
+        assert_eq!(layout, Layout::Paired);
+        assert_eq!(extract(&png, 1).unwrap(), b"Other");
+        assert_eq!(png.image().unwrap(), pixels);
+    }
+
+    #[test]
+    fn test_grows_bit_depth() {
+        let mut png = indexed_png(3, 2);
+        let pixels = png.image().unwrap();
+        embed(&mut png, b"Hi", false, 1).unwrap();
+        assert_eq!(png.ihdr().unwrap().bit_depth, 8);
+        assert_eq!(png.image().unwrap(), pixels);
+        assert_eq!(extract(&png, 1).unwrap(), b"Hi");
+    }
+
+    #[test]
+    fn test_nearest() {
+        let mut png = indexed_png(201, 8);
+        let pixels = png.image().unwrap();
+        let layout = embed(&mut png, b"Message", false, 1).unwrap();
+        assert_eq!(layout, Layout::Nearest);
+        assert_eq!(extract(&png, 1).unwrap(), b"Message");
+        assert_eq!(png.chunk_by_type("PLTE").unwrap().data().len(), 256 * 3);
+        // Grays move to a neighbouring level at most, opaque ones stay opaque.
+        let image = png.image().unwrap();
+        for (a, b) in image.data.chunks(4).zip(pixels.data.chunks(4)) {
+            assert!(a[..3]
+                .iter()
+                .zip(b)
+                .all(|(a, b)| (*a as i16 - *b as i16).abs() <= 1));
+            assert_eq!(a[3] == 255, b[3] == 255);
+        }
+    }
+
+    #[test]
+    fn test_arrange_nearest() {
+        // 129 entries: one pair has to share, it is the closest one.
+        let mut entries: Vec<Entry> = (0..129)
+            .map(|i| [(i % 128 * 2) as u8, (i / 128 * 100) as u8, 0, 255])
+            .collect();
+        entries[100] = [1, 0, 0, 255];
+        let (layout, arranged, remap) = arrange(&entries);
+        assert_eq!(layout, Layout::Nearest);
+        assert_eq!(arranged.len(), MAX_ENTRIES);
+        for (i, entry) in entries.iter().enumerate() {
+            let index = remap[i] as usize;
+            assert_eq!(&arranged[index], entry);
+            let twin = arranged[index ^ 1];
+            if i == 0 || i == 100 {
+                assert_eq!(distance(&twin, entry), 1);
+            } else {
+                assert_eq!(&twin, entry);
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut png = indexed_png(5, 8);
+        let before = png.as_bytes();
+        assert!(embed(&mut png, &[0; 100], false, 1).is_err());
+        assert_eq!(png.as_bytes(), before);
+
+        let image = png.image().unwrap();
+        let mut rgb = png;
+        rgb.set_image(&image, false).unwrap();
+        assert!(embed(&mut rgb, b"Hi", false, 1).is_err());
+    }
+}
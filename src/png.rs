@@ -1,34 +1,163 @@
-use crate::{chunk::Chunk, Error, Result, BYTE_SIZE};
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    filter::unfilter,
+    ihdr::{ColorType, Ihdr},
+    image::{deinterlace, Image},
+    meta::Metadata,
+    text::TextChunk,
+    zlib, Error, Result, BYTE_SIZE,
+};
 use std::convert::TryFrom;
 use std::convert::TryInto;
+use std::str::FromStr;
+
+/// What to do with unknown ancillary chunks that are not safe to copy once
+/// the critical chunks of an image have been modified. Such chunks depend on
+/// the image data, so the PNG specification requires editors to drop them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CopyPolicy {
+    Keep,
+    Warn,
+    #[default]
+    Drop,
+}
+
+impl FromStr for CopyPolicy {
+    type Err = Error<'static>;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "keep" => Ok(CopyPolicy::Keep),
+            "warn" => Ok(CopyPolicy::Warn),
+            "drop" => Ok(CopyPolicy::Drop),
+            _ => Err(Error::Custom("Expected keep, warn or drop")),
+        }
+    }
+}
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Png {
     chunks: Vec<Chunk>,
+    /// Bytes after IEND. Decoders ignore them, so they are kept as is.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Vec::is_empty",
+            with = "crate::chunk::hex"
+        )
+    )]
+    trailer: Vec<u8>,
 }
 
 const HEADER_LENGHT: usize = 8;
 
+/// Size libpng splits image data into, so written files look familiar.
+pub const IDAT_SIZE: usize = 8192;
+
+/// Chunks whose layout depends on the color type of the image.
+const COLOR_TYPE_CHUNKS: [&str; 4] = ["tRNS", "bKGD", "sBIT", "hIST"];
+
+/// Chunks that describe the color space and must come before PLTE.
+const BEFORE_PLTE: [&str; 8] = [
+    "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP", "mDCv", "cLLi",
+];
+
+/// Chunks that must come before the first IDAT.
+const BEFORE_IDAT: [&str; 10] = [
+    "tRNS", "bKGD", "hIST", "pHYs", "sPLT", "oFFs", "pCAL", "sCAL", "sTER", "acTL",
+];
+
+/// Chunks that refer to the palette and must come after PLTE.
+const AFTER_PLTE: [&str; 3] = ["tRNS", "bKGD", "hIST"];
+
+/// Standard ancillary chunks an image may hold more than once.
+const REPEATABLE: [&str; 4] = ["tEXt", "zTXt", "iTXt", "sPLT"];
+
 impl Png {
     const STANDARD_HEADER: [u8; HEADER_LENGHT] = [137, 80, 78, 71, 13, 10, 26, 10];
 
     pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
-        Png { chunks }
+        Png {
+            chunks,
+            trailer: Vec::new(),
+        }
     }
 
     pub fn chunks(&self) -> &[Chunk] {
         &self.chunks
     }
 
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer
+    }
+
+    /// Replaces the data after IEND, an empty trailer removes it.
+    pub fn set_trailer(&mut self, trailer: Vec<u8>) {
+        self.trailer = trailer;
+    }
+
     pub fn append_chunk(&mut self, chunk: Chunk) {
         self.chunks.push(chunk);
     }
 
+    /// Inserts the chunk right before IEND, or at the end if there is none.
+    pub fn insert_before_end(&mut self, chunk: Chunk) {
+        let pos = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().bytes() == b"IEND")
+            .unwrap_or(self.chunks.len());
+        self.chunks.insert(pos, chunk);
+    }
+
+    /// Inserts an ancillary chunk where the PNG specification allows it:
+    /// color space chunks before PLTE, other chunks describing the pixels
+    /// before the first IDAT and everything else right before IEND. A
+    /// standard chunk that may only appear once replaces the existing one,
+    /// which is returned.
+    pub fn insert_chunk(&mut self, chunk: Chunk) -> Option<Chunk> {
+        let name = chunk.chunk_type().to_string();
+        let replaced = if chunk.chunk_type().is_standard() && !REPEATABLE.contains(&name.as_str()) {
+            self.remove_chunk(&name).ok()
+        } else {
+            None
+        };
+        let before: &[&[u8; 4]] = if BEFORE_PLTE.contains(&name.as_str()) {
+            &[b"PLTE", b"IDAT", b"IEND"]
+        } else if BEFORE_IDAT.contains(&name.as_str()) {
+            &[b"IDAT", b"IEND"]
+        } else {
+            &[b"IEND"]
+        };
+        let pos = self
+            .chunks
+            .iter()
+            .position(|chunk| before.contains(&chunk.chunk_type().bytes()))
+            .unwrap_or(self.chunks.len());
+        self.chunks.insert(pos, chunk);
+        replaced
+    }
+
     #[allow(dead_code)]
     fn header(&self) -> &[u8; HEADER_LENGHT] {
         &Png::STANDARD_HEADER
     }
 
+    /// Offset of IEND in the file, which is where [`Png::insert_before_end`]
+    /// puts new chunks.
+    pub fn end_offset(&self) -> usize {
+        HEADER_LENGHT
+            + self
+                .chunks
+                .iter()
+                .take_while(|chunk| chunk.chunk_type().bytes() != b"IEND")
+                .map(|chunk| chunk.data().len() + 3 * BYTE_SIZE)
+                .sum::<usize>()
+    }
+
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
         self.chunks
             .iter()
@@ -47,6 +176,326 @@ impl Png {
         }
     }
 
+    /// Mutable access to a chunk by its 0-based index. [`Chunk::set_data`]
+    /// and [`Chunk::set_chunk_type`] keep its CRC valid.
+    pub fn chunk_mut(&mut self, index: usize) -> Option<&mut Chunk> {
+        self.chunks.get_mut(index)
+    }
+
+    /// Moves the chunk at `from` so it ends up at index `to`, shifting the
+    /// chunks in between.
+    pub fn move_chunk(&mut self, from: usize, to: usize) -> Result<()> {
+        if from >= self.chunks.len() || to >= self.chunks.len() {
+            return Err(Error::Custom("Chunk index out of range"));
+        }
+        let chunk = self.chunks.remove(from);
+        self.chunks.insert(to, chunk);
+        Ok(())
+    }
+
+    /// Checks the order the PNG specification requires: IHDR first, IEND
+    /// last, PLTE before the image data, all IDAT chunks in a row, the
+    /// ancillary chunks [`Png::insert_chunk`] places early before PLTE or
+    /// the first IDAT and the chunks referring to the palette after PLTE.
+    pub fn check_order(&self) -> Result<()> {
+        let types: Vec<&[u8; 4]> = self
+            .chunks
+            .iter()
+            .map(|chunk| chunk.chunk_type().bytes())
+            .collect();
+        let position = |name: &[u8; 4]| types.iter().position(|&t| t == name);
+        let idat: Vec<usize> = (0..types.len()).filter(|&i| types[i] == b"IDAT").collect();
+        if types.first() != Some(&b"IHDR") {
+            return Err(Error::Custom("IHDR must be the first chunk"));
+        }
+        if types.last() != Some(&b"IEND") {
+            return Err(Error::Custom("IEND must be the last chunk"));
+        }
+        if let (Some(plte), Some(&first)) = (position(b"PLTE"), idat.first()) {
+            if plte > first {
+                return Err(Error::Custom("PLTE must come before the image data"));
+            }
+        }
+        if idat.windows(2).any(|pair| pair[1] != pair[0] + 1) {
+            return Err(Error::Custom("IDAT chunks must follow each other"));
+        }
+        let palette = position(b"PLTE");
+        let plte = palette.or_else(|| idat.first().copied());
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            let name = chunk.chunk_type().to_string();
+            if AFTER_PLTE.contains(&name.as_str()) && palette.is_some_and(|palette| i < palette) {
+                return Err(Error::Custom("tRNS, bKGD and hIST must come after PLTE"));
+            }
+            if BEFORE_PLTE.contains(&name.as_str()) && plte.is_some_and(|plte| i > plte) {
+                return Err(Error::Custom(
+                    "Color space chunks must come before PLTE and the image data",
+                ));
+            }
+            if BEFORE_IDAT.contains(&name.as_str()) && idat.first().is_some_and(|&first| i > first)
+            {
+                return Err(Error::Custom(
+                    "Chunks describing the pixels must come before the image data",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn ihdr(&self) -> Result<Ihdr> {
+        let chunk = self
+            .chunk_by_type("IHDR")
+            .ok_or(Error::Custom("Missing IHDR chunk"))?;
+        Ihdr::try_from(chunk)
+    }
+
+    /// Compressed image data, the concatenated data of every IDAT chunk.
+    pub fn idat(&self) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.chunk_type().bytes() == b"IDAT")
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect()
+    }
+
+    /// Decompressed, still filtered image data.
+    pub fn image_data(&self) -> Result<Vec<u8>> {
+        zlib::decompress(&self.idat())
+    }
+
+    /// Replaces all IDAT chunks with `compressed` split into chunks of at
+    /// most `chunk_size` bytes, at the place of the first IDAT.
+    pub fn replace_idat(&mut self, compressed: &[u8], chunk_size: usize) -> Result<()> {
+        let pos = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().bytes() == b"IDAT")
+            .ok_or(Error::Custom("Missing IDAT chunk"))?;
+        self.chunks
+            .retain(|chunk| chunk.chunk_type().bytes() != b"IDAT");
+        let idat = ChunkType::from_str("IDAT")?;
+        let chunks = compressed
+            .chunks(chunk_size.max(1))
+            .map(|data| Chunk::new(idat.clone(), data.to_vec()));
+        self.chunks.splice(pos..pos, chunks);
+        Ok(())
+    }
+
+    /// Decodes the pixels, expanding palette images to RGB, or RGBA when
+    /// the palette has transparency.
+    pub fn image(&self) -> Result<Image> {
+        let image = self.stored_image()?;
+        if image.color_type != ColorType::Indexed {
+            return Ok(image);
+        }
+        let palette = self
+            .chunk_by_type("PLTE")
+            .ok_or(Error::Custom("Indexed image without a PLTE chunk"))?;
+        let transparency = self.chunk_by_type("tRNS").map(|chunk| chunk.data());
+        image.expand_palette(palette.data(), transparency)
+    }
+
+    /// Decodes the pixels as they are stored, palette images keep their
+    /// indices. Interlaced images are returned as ordinary rows.
+    pub fn stored_image(&self) -> Result<Image> {
+        let ihdr = self.ihdr()?;
+        if ihdr.compression_method != 0 || ihdr.filter_method != 0 || ihdr.interlace_method > 1 {
+            return Err(Error::Custom(
+                "Unknown compression, filter or interlace method",
+            ));
+        }
+        let raw = unfilter(&self.image_data()?, &ihdr)?;
+        let data = if ihdr.is_interlaced() {
+            deinterlace(&raw, &ihdr)
+        } else {
+            raw
+        };
+        Image::new(
+            ihdr.width,
+            ihdr.height,
+            ihdr.color_type,
+            ihdr.bit_depth,
+            data,
+        )
+    }
+
+    /// Replaces the pixels and the IHDR chunk. When the color type changes,
+    /// chunks that only make sense for the old one are removed.
+    pub fn set_image(&mut self, image: &Image, interlaced: bool) -> Result<()> {
+        let old = self.ihdr()?;
+        if image.color_type == ColorType::Indexed && self.chunk_by_type("PLTE").is_none() {
+            return Err(Error::Custom("Indexed image without a PLTE chunk"));
+        }
+        let ihdr = image.ihdr(interlaced);
+        let idat = image.compress(&ihdr);
+        self.replace_idat(&idat, IDAT_SIZE)?;
+        let pos = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().bytes() == b"IHDR")
+            .expect("IHDR was decoded above");
+        self.chunks[pos] = ihdr.to_chunk();
+
+        if old.color_type != image.color_type {
+            self.chunks.retain(|chunk| {
+                let name = chunk.chunk_type().to_string();
+                !COLOR_TYPE_CHUNKS.contains(&name.as_str())
+                    && (name != "PLTE"
+                        || !matches!(
+                            image.color_type,
+                            ColorType::Grayscale | ColorType::GrayscaleAlpha
+                        ))
+            });
+        }
+        Ok(())
+    }
+
+    /// Replaces the data of PLTE and tRNS after the palette was rearranged,
+    /// `remap` holding the new index of every old entry. The background color
+    /// in bKGD follows its entry, hIST no longer matches and is removed.
+    pub fn set_palette(
+        &mut self,
+        palette: Vec<u8>,
+        transparency: Option<Vec<u8>>,
+        remap: &[u8],
+    ) -> Result<()> {
+        let pos = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().bytes() == b"PLTE")
+            .ok_or(Error::Custom("Missing PLTE chunk"))?;
+        self.chunks[pos] = Chunk::new(ChunkType::from_str("PLTE")?, palette);
+        for chunk in self.chunks.iter_mut() {
+            if chunk.chunk_type().bytes() == b"bKGD" && chunk.data().len() == 1 {
+                let index = remap.get(chunk.data()[0] as usize).copied().unwrap_or(0);
+                *chunk = Chunk::new(chunk.chunk_type().clone(), vec![index]);
+            }
+        }
+        self.chunks.retain(|chunk| {
+            chunk.chunk_type().bytes() != b"tRNS" && chunk.chunk_type().bytes() != b"hIST"
+        });
+        if let Some(alpha) = transparency {
+            let pos = self
+                .chunks
+                .iter()
+                .position(|chunk| chunk.chunk_type().bytes() == b"PLTE")
+                .expect("PLTE was replaced above");
+            self.chunks
+                .insert(pos + 1, Chunk::new(ChunkType::from_str("tRNS")?, alpha));
+        }
+        Ok(())
+    }
+
+    /// Decodes every tEXt, zTXt and iTXt chunk in file order.
+    pub fn text_chunks(&self) -> Result<Vec<TextChunk>> {
+        self.chunks
+            .iter()
+            .filter(|chunk| TextChunk::is_text_chunk(chunk))
+            .map(TextChunk::try_from)
+            .collect()
+    }
+
+    pub fn add_text(&mut self, text: &TextChunk) -> Result<()> {
+        self.insert_before_end(text.to_chunk()?);
+        Ok(())
+    }
+
+    /// Replaces the first text chunk with the same keyword, keeping its place.
+    pub fn replace_text(&mut self, text: &TextChunk) -> Result<()> {
+        let pos = self.text_position(&text.keyword)?;
+        self.chunks[pos] = text.to_chunk()?;
+        Ok(())
+    }
+
+    /// Removes every text chunk with the given keyword.
+    pub fn remove_text(&mut self, keyword: &str) -> Result<Vec<TextChunk>> {
+        let mut removed = Vec::new();
+        while let Ok(pos) = self.text_position(keyword) {
+            removed.push(TextChunk::try_from(&self.chunks.remove(pos))?);
+        }
+        if removed.is_empty() {
+            return Err(Error::Custom("Unable to find text chunk"));
+        }
+        Ok(removed)
+    }
+
+    fn text_position(&self, keyword: &str) -> Result<usize> {
+        self.chunks
+            .iter()
+            .position(|chunk| {
+                TextChunk::is_text_chunk(chunk)
+                    && TextChunk::try_from(chunk).is_ok_and(|text| text.keyword == keyword)
+            })
+            .ok_or(Error::Custom("Unable to find text chunk"))
+    }
+
+    /// Decodes the first chunk holding `T`, if there is one.
+    pub fn meta<T: Metadata>(&self) -> Result<Option<T>> {
+        self.chunk_by_type(T::CHUNK_TYPE)
+            .map(T::from_chunk)
+            .transpose()
+    }
+
+    /// Replaces the chunk holding `T` or, if there is none, inserts a new one
+    /// right after IHDR where every metadata chunk is allowed to live.
+    pub fn set_meta<T: Metadata>(&mut self, value: &T) -> Result<()> {
+        let chunk = value.to_chunk()?;
+        match self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type().to_string() == T::CHUNK_TYPE)
+        {
+            Some(pos) => self.chunks[pos] = chunk,
+            None => {
+                let pos = self.chunks.len().min(1);
+                self.chunks.insert(pos, chunk);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes every ancillary chunk whose type is not listed in `keep` and
+    /// returns the removed chunks in file order. Critical chunks the PNG
+    /// standard does not define cannot be image data and are removed as well.
+    pub fn strip(&mut self, keep: &[String]) -> Result<Vec<Chunk>> {
+        const CRITICAL: [&[u8; 4]; 4] = [b"IHDR", b"PLTE", b"IDAT", b"IEND"];
+
+        let doomed: Vec<String> = self
+            .chunks
+            .iter()
+            .map(|chunk| chunk.chunk_type())
+            .filter(|chunk_type| {
+                !chunk_type.is_critical() || !CRITICAL.contains(&chunk_type.bytes())
+            })
+            .map(|chunk_type| chunk_type.to_string())
+            .filter(|chunk_type| !keep.contains(chunk_type))
+            .collect();
+        doomed
+            .iter()
+            .map(|chunk_type| self.remove_chunk(chunk_type))
+            .collect()
+    }
+
+    /// Applies `policy` after critical chunks have been modified and returns
+    /// the types of the unknown, unsafe-to-copy chunks it found. With
+    /// [`CopyPolicy::Drop`] those chunks are removed from the image.
+    pub fn apply_copy_policy(&mut self, policy: CopyPolicy) -> Vec<ChunkType> {
+        fn is_unsafe(chunk: &Chunk) -> bool {
+            let chunk_type = chunk.chunk_type();
+            !chunk_type.is_critical() && !chunk_type.is_safe_to_copy() && !chunk_type.is_standard()
+        }
+
+        let found = self
+            .chunks
+            .iter()
+            .filter(|chunk| is_unsafe(chunk))
+            .map(|chunk| chunk.chunk_type().clone())
+            .collect();
+        if policy == CopyPolicy::Drop {
+            self.chunks.retain(|chunk| !is_unsafe(chunk));
+        }
+        found
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         [
             Png::STANDARD_HEADER.to_vec(),
@@ -54,6 +503,7 @@ impl Png {
                 .iter()
                 .flat_map(|chunk| chunk.as_bytes())
                 .collect(),
+            self.trailer.clone(),
         ]
         .concat()
     }
@@ -71,10 +521,17 @@ impl TryFrom<&[u8]> for Png {
         while pivot < value.len() {
             let lenght = u32::from_be_bytes(value[pivot..pivot + BYTE_SIZE].try_into()?) as usize;
             let offset = lenght + 3 * BYTE_SIZE;
-            chunks.push(value[pivot..pivot + offset].try_into()?);
+            let chunk: Chunk = value[pivot..pivot + offset].try_into()?;
             pivot += offset;
+            let is_end = chunk.chunk_type().bytes() == b"IEND";
+            chunks.push(chunk);
+            if is_end {
+                break;
+            }
         }
-        Ok(Png::from_chunks(chunks))
+        let mut png = Png::from_chunks(chunks);
+        png.set_trailer(value[pivot..].to_vec());
+        Ok(png)
     }
 }
 
@@ -87,13 +544,11 @@ mod tests {
     use std::str::FromStr;
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -207,6 +662,320 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_move_chunk() {
+        let mut png = testing_png();
+        let names = |png: &Png| -> Vec<String> {
+            png.chunks()
+                .iter()
+                .map(|chunk| chunk.chunk_type().to_string())
+                .collect()
+        };
+        let before = names(&png);
+        png.move_chunk(0, 2).unwrap();
+        assert_eq!(names(&png), [&*before[1], &*before[2], &*before[0]]);
+        png.move_chunk(2, 0).unwrap();
+        assert_eq!(names(&png), before);
+        assert!(png.move_chunk(0, 3).is_err());
+    }
+
+    #[test]
+    fn test_chunk_mut() {
+        let mut png = testing_png();
+        png.chunk_mut(1).unwrap().set_data(b"Changed".to_vec());
+        let bytes = png.as_bytes();
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(png.chunks()[1].data(), b"Changed");
+        let mut png = png;
+        assert!(png.chunk_mut(3).is_none());
+    }
+
+    #[test]
+    fn test_check_order() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.check_order().is_ok());
+
+        let chunk = |name: &str| chunk_from_strings(name, "").unwrap();
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR"),
+            chunk("IDAT"),
+            chunk("tEXt"),
+            chunk("IDAT"),
+            chunk("IEND"),
+        ]);
+        assert!(png.check_order().is_err());
+        png.move_chunk(2, 3).unwrap();
+        assert!(png.check_order().is_ok());
+        png.move_chunk(0, 1).unwrap();
+        assert!(png.check_order().is_err());
+
+        let png = Png::from_chunks(vec![
+            chunk("IHDR"),
+            chunk("IDAT"),
+            chunk("PLTE"),
+            chunk("IEND"),
+        ]);
+        assert!(png.check_order().is_err());
+        let png = Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT")]);
+        assert!(png.check_order().is_err());
+
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR"),
+            chunk("gAMA"),
+            chunk("PLTE"),
+            chunk("bKGD"),
+            chunk("IDAT"),
+            chunk("IEND"),
+        ]);
+        assert!(png.check_order().is_ok());
+        png.move_chunk(1, 2).unwrap();
+        assert!(png.check_order().is_err());
+        png.move_chunk(2, 1).unwrap();
+        png.move_chunk(3, 4).unwrap();
+        assert!(png.check_order().is_err());
+
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR"),
+            chunk("PLTE"),
+            chunk("tRNS"),
+            chunk("IDAT"),
+            chunk("IEND"),
+        ]);
+        assert!(png.check_order().is_ok());
+        png.move_chunk(2, 1).unwrap();
+        assert!(png.check_order().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_png_serde_roundtrip() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let json = serde_json::to_string(&png).unwrap();
+        let actual: Png = serde_json::from_str(&json).unwrap();
+        assert_eq!(actual.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_replace_idat() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let idat = png.idat();
+        let data = png.image_data().unwrap();
+        let ihdr = png.ihdr().unwrap();
+        assert_eq!(
+            data.len(),
+            (ihdr.row_bytes(ihdr.width) + 1) * ihdr.height as usize
+        );
+
+        png.replace_idat(&idat, 100).unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        let count = idat.len().div_ceil(100);
+        assert_eq!(types[..4], ["IHDR", "sRGB", "gAMA", "pHYs"]);
+        assert!(types[4..4 + count].iter().all(|t| t == "IDAT"));
+        assert_eq!(types[4 + count..], ["RuSt", "IEND"]);
+        assert_eq!(png.idat(), idat);
+    }
+
+    #[test]
+    fn test_image() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let image = png.image().unwrap();
+        assert_eq!((image.width, image.height), (50, 50));
+        assert_eq!(image.color_type, ColorType::Rgba);
+        assert_eq!(image.channels(), 4);
+        assert_eq!(image.data.len(), 50 * 50 * 4);
+
+        let encoded = image.to_png(false).unwrap();
+        assert_eq!(encoded.chunks().len(), 3);
+        assert_eq!(encoded.image().unwrap(), image);
+
+        let mut interlaced = Png::try_from(&PNG_FILE[..]).unwrap();
+        interlaced.set_image(&image, true).unwrap();
+        assert!(interlaced.ihdr().unwrap().is_interlaced());
+        assert_eq!(interlaced.image().unwrap(), image);
+        assert!(interlaced.chunk_by_type("RuSt").is_some());
+    }
+
+    #[test]
+    fn test_palette_image() {
+        let indexed =
+            Image::new(4, 2, ColorType::Indexed, 2, vec![0b0001_1000, 0b1000_0000]).unwrap();
+        let mut png = Png::from_chunks(vec![
+            indexed.ihdr(false).to_chunk(),
+            chunk_from_strings("PLTE", "\u{0}\u{0}\u{0}\u{7f}\u{7f}\u{7f}\u{7f}\u{0}\u{0}")
+                .unwrap(),
+            chunk_from_strings("tRNS", "\u{0}").unwrap(),
+            chunk_from_strings("IDAT", "").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        png.set_image(&indexed, false).unwrap();
+        assert_eq!(png.stored_image().unwrap(), indexed);
+        let image = png.image().unwrap();
+        assert_eq!(image.color_type, ColorType::Rgba);
+        assert_eq!(
+            image.row(0),
+            &[0, 0, 0, 0, 127, 127, 127, 255, 127, 0, 0, 255, 0, 0, 0, 0]
+        );
+
+        // Leaving the palette behind drops its transparency.
+        png.set_image(&image, false).unwrap();
+        assert!(png.chunk_by_type("tRNS").is_none());
+        assert!(png.chunk_by_type("PLTE").is_some());
+        assert_eq!(png.image().unwrap(), image);
+    }
+
+    #[test]
+    fn test_end_offset() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.end_offset(), PNG_FILE.len() - 12);
+        assert_eq!(testing_png().end_offset(), testing_png().as_bytes().len());
+    }
+
+    #[test]
+    fn test_trailer() {
+        let bytes = [&PNG_FILE[..], b"secret"].concat();
+        let png = Png::try_from(&bytes[..]).unwrap();
+        let plain = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.chunks().len(), plain.chunks().len());
+        assert_eq!(png.trailer(), b"secret");
+        assert!(plain.trailer().is_empty());
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_trailer_serde_roundtrip() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.set_trailer(b"secret".to_vec());
+        let json = serde_json::to_string(&png).unwrap();
+        assert!(json.contains("\"trailer\":\"736563726574\""));
+        let actual: Png = serde_json::from_str(&json).unwrap();
+        assert_eq!(actual.trailer(), b"secret");
+    }
+
+    #[test]
+    fn test_insert_before_end() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.insert_before_end(chunk_from_strings("TeSt", "Message").unwrap());
+        let chunks = png.chunks();
+        assert_eq!(&chunks[chunks.len() - 2].chunk_type().to_string(), "TeSt");
+        assert_eq!(&chunks[chunks.len() - 1].chunk_type().to_string(), "IEND");
+    }
+
+    #[test]
+    fn test_insert_chunk() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("gAMA", "old").unwrap(),
+            chunk_from_strings("PLTE", "palette").unwrap(),
+            chunk_from_strings("IDAT", "pixels").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        assert!(png
+            .insert_chunk(chunk_from_strings("ruSt", "Message").unwrap())
+            .is_none());
+        assert!(png
+            .insert_chunk(chunk_from_strings("pHYs", "dpi").unwrap())
+            .is_none());
+        assert!(png
+            .insert_chunk(chunk_from_strings("tEXt", "text").unwrap())
+            .is_none());
+        let replaced = png.insert_chunk(chunk_from_strings("gAMA", "new").unwrap());
+        assert_eq!(replaced.unwrap().data(), b"old");
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(
+            types,
+            ["IHDR", "gAMA", "PLTE", "pHYs", "IDAT", "ruSt", "tEXt", "IEND"]
+        );
+        assert_eq!(png.chunk_by_type("gAMA").unwrap().data(), b"new");
+    }
+
+    #[test]
+    fn test_text_chunks() {
+        use crate::text::TextKind;
+
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.add_text(&TextChunk::new(TextKind::Plain, "Title", "Dice"))
+            .unwrap();
+        png.add_text(&TextChunk::new(
+            TextKind::Compressed,
+            "Comment",
+            "Wikipedia",
+        ))
+        .unwrap();
+        assert_eq!(png.text_chunks().unwrap().len(), 2);
+
+        png.replace_text(&TextChunk::new(TextKind::Plain, "Title", "Two dice"))
+            .unwrap();
+        let texts = png.text_chunks().unwrap();
+        assert_eq!(texts[0].keyword, "Title");
+        assert_eq!(texts[0].text, "Two dice");
+
+        let removed = png.remove_text("Comment").unwrap();
+        assert_eq!(removed[0].text, "Wikipedia");
+        assert_eq!(png.text_chunks().unwrap().len(), 1);
+        assert!(png.remove_text("Comment").is_err());
+    }
+
+    #[test]
+    fn test_meta() {
+        use crate::meta::{Gamma, PhysicalDimensions, Time};
+
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let gamma: Gamma = png.meta().unwrap().unwrap();
+        assert!((gamma.0 - 0.45455).abs() < 1e-9);
+        assert!(png.meta::<Time>().unwrap().is_none());
+
+        png.set_meta(&PhysicalDimensions::from_dpi(300.0).unwrap())
+            .unwrap();
+        png.set_meta(&Time::now()).unwrap();
+        let phys: PhysicalDimensions = png.meta().unwrap().unwrap();
+        assert_eq!(phys.pixels_per_unit_x, 11811);
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "tIME");
+        assert_eq!(png.chunks().len(), 8);
+    }
+
+    #[test]
+    fn test_strip() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let removed = png.strip(&["gAMA".to_string()]).unwrap();
+        let removed: Vec<String> = removed
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(removed, vec!["sRGB", "pHYs", "RuSt"]);
+        let left: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(left, vec!["IHDR", "gAMA", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_apply_copy_policy() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("vpAG", "unknown, unsafe").unwrap());
+        png.append_chunk(chunk_from_strings("vpAg", "unknown, safe").unwrap());
+        png.append_chunk(chunk_from_strings("gAMA", "known").unwrap());
+
+        let found = png.apply_copy_policy(CopyPolicy::Warn);
+        assert_eq!(found, vec![ChunkType::from_str("vpAG").unwrap()]);
+        assert_eq!(png.chunks().len(), 6);
+
+        let found = png.apply_copy_policy(CopyPolicy::Drop);
+        assert_eq!(found.len(), 1);
+        assert_eq!(png.chunks().len(), 5);
+        assert!(png.chunk_by_type("vpAG").is_none());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -217,7 +986,7 @@ mod tests {
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected: Vec<u8> = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
     }
 
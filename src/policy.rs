@@ -0,0 +1,93 @@
+use std::fmt;
+
+use crate::chunk_type::ChunkType;
+
+/// Reason a chunk type must not carry a hidden payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// The type is defined by the PNG specification, so decoders interpret it.
+    Standard,
+    /// Decoders refuse to display images with unknown critical chunks.
+    Critical,
+    /// Public types are reserved for chunks registered with the specification.
+    Public,
+    /// The reserved bit is set, which makes the type invalid.
+    Reserved,
+}
+
+impl Violation {
+    pub fn message(self) -> &'static str {
+        match self {
+            Violation::Standard => "Refusing to hide a payload in a standard chunk type",
+            Violation::Critical => "Refusing to hide a payload in a critical chunk type",
+            Violation::Public => "Refusing to hide a payload in a public chunk type",
+            Violation::Reserved => {
+                "Refusing to hide a payload in a chunk type with the reserved bit set"
+            }
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+/// Checks that a payload stored in `chunk_type` leaves the image intact and
+/// does not collide with chunks other software understands.
+pub fn check_payload_type(chunk_type: &ChunkType) -> Result<(), Violation> {
+    if chunk_type.is_standard() {
+        Err(Violation::Standard)
+    } else if chunk_type.is_critical() {
+        Err(Violation::Critical)
+    } else if chunk_type.is_public() {
+        Err(Violation::Public)
+    } else if !chunk_type.is_reserved_bit_valid() {
+        Err(Violation::Reserved)
+    } else {
+        Ok(())
+    }
+}
+
+/// Ancillary, private and safe-to-copy variant of `chunk_type` with the same letters.
+pub fn suggest_payload_type(chunk_type: &ChunkType) -> ChunkType {
+    let name = std::str::from_utf8(chunk_type.bytes()).expect("chunk types are ASCII");
+    // Standard types are all public, so a private variant is never standard.
+    ChunkType::builder(name)
+        .ancillary(true)
+        .private(true)
+        .safe_to_copy(true)
+        .build()
+        .expect("built from a valid chunk type")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn check(name: &str) -> Result<(), Violation> {
+        check_payload_type(&ChunkType::from_str(name).unwrap())
+    }
+
+    #[test]
+    fn test_check_payload_type() {
+        assert_eq!(check("IDAT"), Err(Violation::Standard));
+        assert_eq!(check("tEXt"), Err(Violation::Standard));
+        assert_eq!(check("RuST"), Err(Violation::Critical));
+        assert_eq!(check("rUSt"), Err(Violation::Public));
+        assert_eq!(check("ruse"), Err(Violation::Reserved));
+        assert_eq!(check("ruSt"), Ok(()));
+    }
+
+    #[test]
+    fn test_suggest_payload_type() {
+        let suggestion = suggest_payload_type(&ChunkType::from_str("IDAT").unwrap());
+        assert_eq!(&suggestion.to_string(), "idAt");
+        assert_eq!(check_payload_type(&suggestion), Ok(()));
+
+        let suggestion = suggest_payload_type(&ChunkType::from_str("RuST").unwrap());
+        assert_eq!(&suggestion.to_string(), "ruSt");
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+
+/// Number of occurrences of every byte value.
+pub fn histogram(data: &[u8]) -> [usize; 256] {
+    let mut counts = [0; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    counts
+}
+
+/// Shannon entropy in bits per byte, from 0 for a single repeated value to 8
+/// for uniformly random data.
+pub fn entropy(data: &[u8]) -> f64 {
+    let len = data.len() as f64;
+    histogram(data)
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Highest entropy `len` bytes can reach, which is below 8 bits for data
+/// shorter than 256 bytes.
+pub fn max_entropy(len: usize) -> f64 {
+    (len.min(256) as f64).log2()
+}
+
+/// Share of bytes that are printable ASCII or common whitespace.
+pub fn printable_ratio(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let printable = data
+        .iter()
+        .filter(|&&b| b.is_ascii_graphic() || matches!(b, b' ' | b'\t' | b'\n' | b'\r'))
+        .count();
+    printable as f64 / data.len() as f64
+}
+
+/// Byte values shown by the [`ByteStats`] summary.
+const TOP_VALUES: usize = 3;
+
+/// Statistics that tell text, compressed and encrypted data apart: text has
+/// a high printable ratio and an entropy around 4 to 5 bits, compressed or
+/// encrypted data is close to 8 bits and hardly printable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ByteStats {
+    pub entropy: f64,
+    pub distinct: usize,
+    pub printable: f64,
+    /// Most frequent byte values with their counts, most frequent first.
+    pub top: Vec<(u8, usize)>,
+    len: usize,
+}
+
+impl ByteStats {
+    pub fn new(data: &[u8]) -> ByteStats {
+        let histogram = histogram(data);
+        let mut counts: Vec<(u8, usize)> = (0..=255u8)
+            .map(|value| (value, histogram[value as usize]))
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        let distinct = counts.len();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts.truncate(TOP_VALUES);
+        ByteStats {
+            entropy: entropy(data),
+            distinct,
+            printable: printable_ratio(data),
+            top: counts,
+            len: data.len(),
+        }
+    }
+}
+
+impl fmt::Display for ByteStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "  Entropy: {:.2} bits per byte, {} distinct values, {:.0}% printable",
+            self.entropy,
+            self.distinct,
+            self.printable * 100.0
+        )?;
+        if !self.top.is_empty() {
+            let top: Vec<String> = self
+                .top
+                .iter()
+                .map(|&(value, count)| {
+                    format!(
+                        "{:02x} ({:.1}%)",
+                        value,
+                        count as f64 * 100.0 / self.len as f64
+                    )
+                })
+                .collect();
+            writeln!(f, "  Most common: {}", top.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(&[]), 0.0);
+        assert_eq!(entropy(b"aaaa"), 0.0);
+        assert_eq!(entropy(b"abab"), 1.0);
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(entropy(&all), 8.0);
+        assert_eq!(max_entropy(all.len()), 8.0);
+        assert_eq!(max_entropy(4), 2.0);
+    }
+
+    #[test]
+    fn test_byte_stats() {
+        let stats = ByteStats::new(b"aab\x00");
+        assert_eq!(stats.distinct, 3);
+        assert_eq!(stats.printable, 0.75);
+        assert_eq!(stats.top, vec![(b'a', 2), (0, 1), (b'b', 1)]);
+        assert_eq!(
+            stats.to_string(),
+            "  Entropy: 1.50 bits per byte, 3 distinct values, 75% printable\n  \
+             Most common: 61 (50.0%), 00 (25.0%), 62 (25.0%)\n"
+        );
+        assert_eq!(ByteStats::new(&[]).to_string().lines().count(), 1);
+    }
+}
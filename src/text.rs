@@ -0,0 +1,231 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, zlib, Error, Result};
+
+/// Which of the three textual chunk types a [`TextChunk`] is stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextKind {
+    /// `tEXt`, uncompressed Latin-1.
+    Plain,
+    /// `zTXt`, zlib compressed Latin-1.
+    Compressed,
+    /// `iTXt`, UTF-8 with an optional language tag, optionally compressed.
+    International { compressed: bool },
+}
+
+impl TextKind {
+    pub fn chunk_type(self) -> &'static str {
+        match self {
+            TextKind::Plain => "tEXt",
+            TextKind::Compressed => "zTXt",
+            TextKind::International { .. } => "iTXt",
+        }
+    }
+}
+
+/// Keyword/text pair stored in a `tEXt`, `zTXt` or `iTXt` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    pub kind: TextKind,
+    pub keyword: String,
+    pub text: String,
+    /// RFC 3066 language tag, `iTXt` only.
+    pub language_tag: String,
+    /// Keyword translated into the language of the text, `iTXt` only.
+    pub translated_keyword: String,
+}
+
+impl TextChunk {
+    pub fn new(kind: TextKind, keyword: &str, text: &str) -> TextChunk {
+        TextChunk {
+            kind,
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            language_tag: String::new(),
+            translated_keyword: String::new(),
+        }
+    }
+
+    pub fn is_text_chunk(chunk: &Chunk) -> bool {
+        matches!(chunk.chunk_type().bytes(), b"tEXt" | b"zTXt" | b"iTXt")
+    }
+
+    /// Whether the text can be stored in a Latin-1 `tEXt` or `zTXt` chunk.
+    pub fn is_latin1(text: &str) -> bool {
+        text.chars().all(|c| (c as u32) < 0x100)
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let keyword = to_latin1(&self.keyword)?;
+        if keyword.is_empty() || keyword.len() > 79 || keyword.contains(&0) {
+            return Err(Error::Custom("Keyword must be 1-79 characters long"));
+        }
+        let mut data = keyword;
+        data.push(0);
+        match self.kind {
+            TextKind::Plain => data.extend(to_latin1(&self.text)?),
+            TextKind::Compressed => {
+                data.push(0);
+                data.extend(zlib::compress(&to_latin1(&self.text)?));
+            }
+            TextKind::International { compressed } => {
+                if self.language_tag.contains('\0') || self.translated_keyword.contains('\0') {
+                    return Err(Error::Custom("iTXt fields must not contain NUL"));
+                }
+                data.push(compressed as u8);
+                data.push(0);
+                data.extend(self.language_tag.as_bytes());
+                data.push(0);
+                data.extend(self.translated_keyword.as_bytes());
+                data.push(0);
+                if compressed {
+                    data.extend(zlib::compress(self.text.as_bytes()));
+                } else {
+                    data.extend(self.text.as_bytes());
+                }
+            }
+        }
+        Ok(Chunk::new(
+            ChunkType::from_str(self.kind.chunk_type())?,
+            data,
+        ))
+    }
+}
+
+pub(crate) fn to_latin1(s: &str) -> Result<Vec<u8>> {
+    if !TextChunk::is_latin1(s) {
+        return Err(Error::Custom("Text is not representable in Latin-1"));
+    }
+    Ok(s.chars().map(|c| c as u8).collect())
+}
+
+pub(crate) fn from_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// Splits off the NUL terminated field at the start of `data`.
+pub(crate) fn split_nul(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    match data.iter().position(|&b| b == 0) {
+        Some(pos) => Ok((&data[..pos], &data[pos + 1..])),
+        None => Err(Error::Custom("Missing NUL separator in chunk data")),
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = Error<'static>;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let (keyword, rest) = split_nul(chunk.data())?;
+        let keyword = from_latin1(keyword);
+        match chunk.chunk_type().bytes() {
+            b"tEXt" => Ok(TextChunk::new(
+                TextKind::Plain,
+                &keyword,
+                &from_latin1(rest),
+            )),
+            b"zTXt" => {
+                let (&method, text) = rest
+                    .split_first()
+                    .ok_or(Error::Custom("zTXt chunk is truncated"))?;
+                if method != 0 {
+                    return Err(Error::Custom("Unknown compression method"));
+                }
+                let text = from_latin1(&zlib::decompress(text)?);
+                Ok(TextChunk::new(TextKind::Compressed, &keyword, &text))
+            }
+            b"iTXt" => {
+                if rest.len() < 2 {
+                    return Err(Error::Custom("iTXt chunk is truncated"));
+                }
+                let compressed = rest[0] != 0;
+                if compressed && rest[1] != 0 {
+                    return Err(Error::Custom("Unknown compression method"));
+                }
+                let (language_tag, rest) = split_nul(&rest[2..])?;
+                let (translated_keyword, text) = split_nul(rest)?;
+                let text = if compressed {
+                    String::from_utf8(zlib::decompress(text)?)?
+                } else {
+                    std::str::from_utf8(text)?.to_string()
+                };
+                Ok(TextChunk {
+                    kind: TextKind::International { compressed },
+                    keyword,
+                    text,
+                    language_tag: std::str::from_utf8(language_tag)?.to_string(),
+                    translated_keyword: std::str::from_utf8(translated_keyword)?.to_string(),
+                })
+            }
+            _ => Err(Error::Custom("Chunk is not a text chunk")),
+        }
+    }
+}
+
+impl fmt::Display for TextChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}", self.keyword, self.kind.chunk_type())?;
+        if !self.language_tag.is_empty() {
+            write!(f, ", {}", self.language_tag)?;
+        }
+        if !self.translated_keyword.is_empty() {
+            write!(f, ", \"{}\"", self.translated_keyword)?;
+        }
+        write!(f, "): {}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(text: &TextChunk) -> TextChunk {
+        let chunk = text.to_chunk().unwrap();
+        TextChunk::try_from(&chunk).unwrap()
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("tEXt").unwrap(),
+            b"Title\0D\xeece".to_vec(),
+        );
+        let text = TextChunk::try_from(&chunk).unwrap();
+        assert_eq!(text.kind, TextKind::Plain);
+        assert_eq!(text.keyword, "Title");
+        assert_eq!(text.text, "Dîce");
+        assert_eq!(text.to_chunk().unwrap().as_bytes(), chunk.as_bytes());
+    }
+
+    #[test]
+    fn test_compressed_text() {
+        let text = TextChunk::new(TextKind::Compressed, "Comment", "Lorem ipsum");
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(&chunk.chunk_type().to_string(), "zTXt");
+        assert_eq!(roundtrip(&text), text);
+    }
+
+    #[test]
+    fn test_international_text() {
+        for &compressed in &[false, true] {
+            let mut text =
+                TextChunk::new(TextKind::International { compressed }, "Title", "Würfel ✓");
+            text.language_tag = "de".to_string();
+            text.translated_keyword = "Titel".to_string();
+            assert_eq!(roundtrip(&text), text);
+        }
+    }
+
+    #[test]
+    fn test_invalid_text() {
+        let text = TextChunk::new(TextKind::Plain, "Title", "✓");
+        assert!(text.to_chunk().is_err());
+
+        let text = TextChunk::new(TextKind::Plain, "", "text");
+        assert!(text.to_chunk().is_err());
+
+        let chunk = Chunk::new(ChunkType::from_str("tEXt").unwrap(), b"Title".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+    }
+}
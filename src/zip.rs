@@ -0,0 +1,183 @@
+use std::convert::TryFrom;
+use std::io::Write;
+
+use flate2::{write::DeflateEncoder, Compression};
+
+use crate::{Error, Result};
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const VERSION: u16 = 20;
+const UTF8_NAMES: u16 = 1 << 11;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+/// 1980-01-01 00:00, the earliest date a zip file can hold.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+struct Entry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+fn size_u32(size: usize) -> Result<u32> {
+    u32::try_from(size)
+        .map_err(|_| Error::Custom("Zip archives without Zip64 are limited to 4 GiB"))
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data).expect("in-memory deflate stream");
+    encoder.finish().expect("in-memory deflate stream")
+}
+
+/// Writes a zip archive of `files` that starts `base_offset` bytes into the
+/// file it will be embedded in, so every offset stored in the archive points
+/// at the right place of the whole file. The end of central directory record
+/// declares a comment of `trailing` bytes, which lets the data that follows
+/// the archive in the file (e.g. IEND) pass for that comment.
+pub fn archive(
+    files: &[(String, Vec<u8>)],
+    base_offset: usize,
+    trailing: usize,
+) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut entries = Vec::with_capacity(files.len());
+    for (name, data) in files {
+        let compressed = deflate(data);
+        let (method, body) = if compressed.len() < data.len() {
+            (DEFLATED, &compressed[..])
+        } else {
+            (STORED, &data[..])
+        };
+        let entry = Entry {
+            name: name.clone(),
+            method,
+            crc: crc::crc32::checksum_ieee(data),
+            compressed_size: size_u32(body.len())?,
+            size: size_u32(data.len())?,
+            offset: size_u32(base_offset + out.len())?,
+        };
+        out.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+        write_common(&mut out, &entry)?;
+        out.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        out.extend_from_slice(entry.name.as_bytes());
+        out.extend_from_slice(body);
+        entries.push(entry);
+    }
+
+    let directory_offset = size_u32(base_offset + out.len())?;
+    let directory_start = out.len();
+    for entry in &entries {
+        out.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+        out.extend_from_slice(&VERSION.to_le_bytes()); // version made by
+        write_common(&mut out, entry)?;
+        out.extend_from_slice(&[0; 12]); // extra, comment, disk, attributes
+        out.extend_from_slice(&entry.offset.to_le_bytes());
+        out.extend_from_slice(entry.name.as_bytes());
+    }
+    let directory_size = size_u32(out.len() - directory_start)?;
+
+    let count = u16::try_from(entries.len())
+        .map_err(|_| Error::Custom("Zip archives without Zip64 hold at most 65535 files"))?;
+    let trailing = u16::try_from(trailing)
+        .map_err(|_| Error::Custom("Too much data follows the zip archive"))?;
+    out.extend_from_slice(&END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+    out.extend_from_slice(&[0; 4]); // disk numbers
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&directory_size.to_le_bytes());
+    out.extend_from_slice(&directory_offset.to_le_bytes());
+    out.extend_from_slice(&trailing.to_le_bytes());
+    Ok(out)
+}
+
+/// Fields shared by local and central headers, from "version needed" up to
+/// the file name length.
+fn write_common(out: &mut Vec<u8>, entry: &Entry) -> Result<()> {
+    let name_length = u16::try_from(entry.name.len())
+        .map_err(|_| Error::Custom("File name is too long for a zip archive"))?;
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&UTF8_NAMES.to_le_bytes());
+    out.extend_from_slice(&entry.method.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // time
+    out.extend_from_slice(&DOS_DATE.to_le_bytes());
+    out.extend_from_slice(&entry.crc.to_le_bytes());
+    out.extend_from_slice(&entry.compressed_size.to_le_bytes());
+    out.extend_from_slice(&entry.size.to_le_bytes());
+    out.extend_from_slice(&name_length.to_le_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn u16_at(data: &[u8], pos: usize) -> usize {
+        u16::from_le_bytes(data[pos..pos + 2].try_into().unwrap()) as usize
+    }
+
+    fn u32_at(data: &[u8], pos: usize) -> usize {
+        u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap()) as usize
+    }
+
+    /// Reads the names and contents back the way an unzip tool does: from the
+    /// end of central directory record at the end of `file`.
+    pub(crate) fn read_archive(file: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let end = (0..file.len() - 21)
+            .rev()
+            .find(|&pos| u32_at(file, pos) == END_OF_CENTRAL_DIRECTORY as usize)
+            .expect("end of central directory");
+        assert_eq!(end + 22 + u16_at(file, end + 20), file.len());
+        let mut pos = u32_at(file, end + 16);
+        let mut files = Vec::new();
+        for _ in 0..u16_at(file, end + 10) {
+            assert_eq!(u32_at(file, pos), CENTRAL_HEADER as usize);
+            let name_length = u16_at(file, pos + 28);
+            let name = String::from_utf8(file[pos + 46..pos + 46 + name_length].to_vec()).unwrap();
+            let local = u32_at(file, pos + 42);
+            assert_eq!(u32_at(file, local), LOCAL_HEADER as usize);
+            let start = local + 30 + u16_at(file, local + 26);
+            let body = &file[start..start + u32_at(file, pos + 20)];
+            let data = match u16_at(file, pos + 10) as u16 {
+                STORED => body.to_vec(),
+                _ => {
+                    let mut data = Vec::new();
+                    std::io::Read::read_to_end(
+                        &mut flate2::read::DeflateDecoder::new(body),
+                        &mut data,
+                    )
+                    .unwrap();
+                    data
+                }
+            };
+            assert_eq!(
+                crc::crc32::checksum_ieee(&data) as usize,
+                u32_at(file, pos + 16)
+            );
+            files.push((name, data));
+            pos += 46 + name_length;
+        }
+        files
+    }
+
+    #[test]
+    fn test_archive() {
+        let files = vec![
+            (
+                "a.txt".to_string(),
+                b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_vec(),
+            ),
+            ("b.bin".to_string(), vec![1, 2, 3]),
+        ];
+        let prefix = b"some image".to_vec();
+        let archive = archive(&files, prefix.len(), 5).unwrap();
+        let file = [prefix, archive, b"IEND!".to_vec()].concat();
+        assert_eq!(read_archive(&file), files);
+    }
+}
@@ -0,0 +1,44 @@
+use std::io::{Read, Write};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::Result;
+
+/// Inflates a zlib stream, the only compression method PNG defines.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    ZlibDecoder::new(data).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    compress_with_level(data, Compression::default().level())
+}
+
+/// Compresses with a zlib level from 0 (store) to 9 (smallest).
+pub fn compress_with_level(data: &[u8], level: u32) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+    // Writing into a Vec cannot fail.
+    encoder.write_all(data).expect("in-memory zlib stream");
+    encoder.finish().expect("in-memory zlib stream")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let data = b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_vec();
+        let compressed = compress(&data);
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress(&compressed).unwrap(), data);
+        assert!(compress_with_level(&data, 0).len() > compressed.len());
+        assert_eq!(decompress(&compress_with_level(&data, 9)).unwrap(), data);
+    }
+
+    #[test]
+    fn test_invalid_stream() {
+        assert!(decompress(&[1, 2, 3, 4]).is_err());
+    }
+}